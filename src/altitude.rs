@@ -0,0 +1,315 @@
+// Copyright 2025. This file is part of TFRAlert.
+
+// TFRAlert is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+// TFRAlert is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License along with TFRAlert. If not, see <https://www.gnu.org/licenses/>.
+
+use crate::logic::Airspace;
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AltitudeReference {
+    Agl,
+    Msl,
+    /// Flight level, hundreds of feet on the standard altimeter setting
+    Fl,
+}
+
+/// A single altitude, always stored in feet ("FL180" is 18000 ft, `Fl`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AltitudeLimit {
+    pub feet: u32,
+    pub reference: AltitudeReference,
+}
+
+impl AltitudeLimit {
+    pub const SURFACE: AltitudeLimit = AltitudeLimit {
+        feet: 0,
+        reference: AltitudeReference::Agl,
+    };
+
+    /// Height above ground, given the ground elevation (MSL) underneath.
+    /// Negative means below ground.
+    pub fn feet_agl(&self, ground_elevation_ft: u32) -> i64 {
+        match self.reference {
+            AltitudeReference::Agl => self.feet as i64,
+            AltitudeReference::Msl | AltitudeReference::Fl => {
+                self.feet as i64 - ground_elevation_ft as i64
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for AltitudeLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.reference {
+            AltitudeReference::Agl if self.feet == 0 => write!(f, "SFC"),
+            AltitudeReference::Agl => write!(f, "{} ft AGL", self.feet),
+            AltitudeReference::Msl => write!(f, "{} ft MSL", self.feet),
+            AltitudeReference::Fl => write!(f, "FL{}", self.feet / 100),
+        }
+    }
+}
+
+/// Vertical extent of an airspace area. No ceiling means unlimited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AltitudeBand {
+    pub floor: AltitudeLimit,
+    pub ceiling: Option<AltitudeLimit>,
+}
+
+impl AltitudeBand {
+    /// Does this band overlap the block of air from the ground up to
+    /// `operating_ceiling_ft` AGL, with the ground at `ground_elevation_ft` MSL?
+    pub fn overlaps(&self, operating_ceiling_ft: u32, ground_elevation_ft: u32) -> bool {
+        let floor_ok = self.floor.feet_agl(ground_elevation_ft) <= operating_ceiling_ft as i64;
+        let ceiling_ok = self
+            .ceiling
+            .is_none_or(|c| c.feet_agl(ground_elevation_ft) > 0);
+        floor_ok && ceiling_ok
+    }
+}
+
+impl std::fmt::Display for AltitudeBand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.ceiling {
+            Some(ceiling) => write!(f, "{}–{}", self.floor, ceiling),
+            None => write!(f, "{}–UNL", self.floor),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AltitudeError(pub String);
+
+impl std::fmt::Display for AltitudeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unrecognized altitude '{}'", self.0)
+    }
+}
+
+impl std::error::Error for AltitudeError {}
+
+/// "FL180", "fl 180" or "flight level 180" as a word of its own, so
+/// "flight" or "flood" elsewhere in the text isn't taken for one
+fn flight_level(text: &str) -> Option<u32> {
+    for prefix in ["flight level", "fl"] {
+        for (i, _) in text.match_indices(prefix) {
+            if text[..i]
+                .chars()
+                .next_back()
+                .is_some_and(char::is_alphanumeric)
+            {
+                continue;
+            }
+            let rest = text[i + prefix.len()..].trim_start();
+            let digits: String = rest.chars().take_while(char::is_ascii_digit).collect();
+            let after = rest[digits.len()..].chars().next();
+            if (2..=3).contains(&digits.len()) && !after.is_some_and(char::is_alphanumeric) {
+                return digits.parse().ok();
+            }
+        }
+    }
+    None
+}
+
+/// One limit, e.g. "3000 feet MSL", "400 FT AGL", "FL180", "the surface".
+/// Feet without a reference are taken as MSL, which is how the FAA
+/// publishes them.
+fn parse_limit(text: &str) -> Option<AltitudeLimit> {
+    let text = text.trim();
+    if text.contains("surface") || text.contains("sfc") {
+        return Some(AltitudeLimit::SURFACE);
+    }
+
+    if let Some(level) = flight_level(text) {
+        return Some(AltitudeLimit {
+            feet: level * 100,
+            reference: AltitudeReference::Fl,
+        });
+    }
+
+    let digits: String = text
+        .chars()
+        .skip_while(|c| !c.is_ascii_digit())
+        .take_while(|c| c.is_ascii_digit() || *c == ',')
+        .filter(|c| *c != ',')
+        .collect();
+    let feet = digits.parse::<u32>().ok()?;
+    let reference = if text.contains("agl") || text.contains("above ground") {
+        AltitudeReference::Agl
+    } else {
+        AltitudeReference::Msl
+    };
+    Some(AltitudeLimit { feet, reference })
+}
+
+/// Altitude text from the detail page, e.g.
+/// "From the surface up to and including 400 feet AGL" or
+/// "From 2000 feet MSL up to but not including FL180".
+pub fn parse_altitude(text: &str) -> Result<AltitudeBand, AltitudeError> {
+    let err = || AltitudeError(text.to_string());
+    let lower = text.to_lowercase();
+
+    let (floor_text, ceiling_text) = match lower.split_once("up to") {
+        Some((floor, ceiling)) => (floor, ceiling),
+        // "SFC-17999FT MSL" style
+        None => lower.split_once('-').ok_or_else(err)?,
+    };
+
+    let floor = if floor_text.trim().is_empty() {
+        AltitudeLimit::SURFACE
+    } else {
+        parse_limit(floor_text).ok_or_else(err)?
+    };
+    let ceiling_text = ceiling_text
+        .replace("and including", "")
+        .replace("but not including", "");
+    let ceiling = if ceiling_text.contains("unl") {
+        None
+    } else {
+        Some(parse_limit(&ceiling_text).ok_or_else(err)?)
+    };
+
+    Ok(AltitudeBand { floor, ceiling })
+}
+
+/// The first area's altitude band that reaches down into the air we fly
+/// in, as a human-readable reason; `None` if all of it is above us.
+/// Areas without a usable altitude count as overlapping.
+pub fn overlapping_band(
+    airspace: &Airspace,
+    operating_ceiling_ft: u32,
+    ground_elevation_ft: u32,
+) -> Option<String> {
+    if airspace.areas.is_empty() {
+        return Some("no altitude given, kept".to_string());
+    }
+
+    airspace
+        .areas
+        .iter()
+        .find_map(|area| match &area.altitude_band {
+            Some(band) if band.overlaps(operating_ceiling_ft, ground_elevation_ft) => {
+                Some(format!("altitude {band}"))
+            }
+            Some(_) => None,
+            None => {
+                warn!(
+                    "No altitude band for airspace area ('{}'), keeping it",
+                    area.altitude
+                );
+                Some("altitude not parsed, kept".to_string())
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logic::AirspaceArea;
+
+    fn limit(feet: u32, reference: AltitudeReference) -> AltitudeLimit {
+        AltitudeLimit { feet, reference }
+    }
+
+    #[test]
+    fn agl_band() {
+        let band = parse_altitude("From the surface up to and including 400 feet AGL").unwrap();
+        assert_eq!(band.floor, AltitudeLimit::SURFACE);
+        assert_eq!(band.ceiling, Some(limit(400, AltitudeReference::Agl)));
+        assert_eq!(band.to_string(), "SFC–400 ft AGL");
+    }
+
+    #[test]
+    fn msl_and_flight_level_bands() {
+        let band = parse_altitude("From 2,000 feet MSL up to but not including FL180").unwrap();
+        assert_eq!(band.floor, limit(2000, AltitudeReference::Msl));
+        assert_eq!(band.ceiling, Some(limit(18000, AltitudeReference::Fl)));
+
+        let band = parse_altitude("SFC-17999FT MSL").unwrap();
+        assert_eq!(band.floor, AltitudeLimit::SURFACE);
+        assert_eq!(band.ceiling, Some(limit(17999, AltitudeReference::Msl)));
+
+        let band = parse_altitude("From flight level 180 up to unlimited").unwrap();
+        assert_eq!(band.floor, limit(18000, AltitudeReference::Fl));
+        assert_eq!(band.ceiling, None);
+        assert_eq!(band.to_string(), "FL180–UNL");
+    }
+
+    #[test]
+    fn flight_only_as_a_word() {
+        let band = parse_altitude("From 3000 feet MSL (no flight below) up to FL 180").unwrap();
+        assert_eq!(band.floor, limit(3000, AltitudeReference::Msl));
+        assert_eq!(band.ceiling, Some(limit(18000, AltitudeReference::Fl)));
+
+        let band = parse_altitude("SFC-2000FT AGL flood control flight ops").unwrap();
+        assert_eq!(band.ceiling, Some(limit(2000, AltitudeReference::Agl)));
+        // too many digits for a flight level
+        assert_eq!(
+            parse_altitude("SFC-FL1800").unwrap().ceiling,
+            Some(limit(1800, AltitudeReference::Msl))
+        );
+    }
+
+    #[test]
+    fn unreadable_altitude() {
+        for text in ["", "See remarks", "From the surface up to lots"] {
+            assert!(parse_altitude(text).is_err(), "{text}");
+        }
+        assert_eq!(
+            parse_altitude("high").unwrap_err().to_string(),
+            "unrecognized altitude 'high'"
+        );
+    }
+
+    #[test]
+    fn overlap_against_the_ceiling() {
+        let band = |text| parse_altitude(text).unwrap();
+
+        // we fly up to 1000 ft AGL over ground at 500 ft MSL
+        assert!(band("SFC-400FT AGL").overlaps(1000, 500));
+        assert!(band("1000FT AGL-3000FT AGL").overlaps(1000, 500));
+        assert!(!band("1001FT AGL-3000FT AGL").overlaps(1000, 500));
+        // MSL limits are measured from the ground
+        assert!(band("1500FT MSL-3000FT MSL").overlaps(1000, 500));
+        assert!(!band("1600FT MSL-3000FT MSL").overlaps(1000, 500));
+        assert!(!band("FL180-UNL").overlaps(1000, 500));
+        // a ceiling below the ground is nothing we can fly into
+        assert!(!band("SFC-400FT MSL").overlaps(1000, 500));
+    }
+
+    #[test]
+    fn overlapping_band_reasons() {
+        let airspace = |altitudes: &[&str]| Airspace {
+            areas: altitudes
+                .iter()
+                .map(|altitude| {
+                    let mut area = AirspaceArea {
+                        altitude: altitude.to_string(),
+                        ..Default::default()
+                    };
+                    area.resolve_altitude();
+                    area
+                })
+                .collect(),
+            effective: Vec::new(),
+        };
+
+        let shelves = airspace(&["FL180-UNL", "SFC-3000FT MSL"]);
+        assert_eq!(
+            overlapping_band(&shelves, 1000, 0).as_deref(),
+            Some("altitude SFC–3000 ft MSL")
+        );
+        assert_eq!(overlapping_band(&airspace(&["FL180-UNL"]), 1000, 0), None);
+        assert_eq!(
+            overlapping_band(&airspace(&["See remarks"]), 1000, 0).as_deref(),
+            Some("altitude not parsed, kept")
+        );
+        assert!(overlapping_band(&airspace(&[]), 1000, 0).is_some());
+    }
+}
@@ -0,0 +1,266 @@
+// Copyright 2025. This file is part of TFRAlert.
+
+// TFRAlert is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+// TFRAlert is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License along with TFRAlert. If not, see <https://www.gnu.org/licenses/>.
+
+//! Capture archives: every check's downloads, gzipped, in a directory named
+//! for when the check ran (`20251001T143200Z/exportTfrList.json.gz`,
+//! `.../details/5_1234.html.gz`). Each one is a `ReplaySource` directory, so
+//! a past day can be checked again, at the time it was captured, with
+//! today's parser.
+
+use crate::logic::{self, CheckResult, MatchCriteria};
+use crate::source::{DETAILS_DIR, FEED_FILE, FeedSource, ReplaySource, detail_file_name};
+use crate::store::SqliteStore;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use flate2::{Compression, write::GzEncoder};
+use log::{debug, info, warn};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// To the millisecond, so checks run close together don't share a directory
+const SNAPSHOT_FORMAT: &str = "%Y%m%dT%H%M%S%.3fZ";
+
+/// Passes everything through from `inner`, keeping a copy in one snapshot
+/// directory of the archive
+pub struct Recorder<'a, S> {
+    inner: &'a S,
+    dir: PathBuf,
+}
+
+impl<'a, S: FeedSource> Recorder<'a, S> {
+    pub fn new(inner: &'a S, archive: &Path, at: DateTime<Utc>) -> Result<Self> {
+        let dir = archive.join(at.format(SNAPSHOT_FORMAT).to_string());
+        fs::create_dir_all(dir.join(DETAILS_DIR))
+            .with_context(|| format!("Failed to create capture directory {}", dir.display()))?;
+        Ok(Recorder { inner, dir })
+    }
+
+    /// A failed write is logged, not passed on: the check itself went fine
+    fn save(&self, path: PathBuf, content: &str) {
+        let mut name = path.into_os_string();
+        name.push(".gz");
+        let path = PathBuf::from(name);
+        let written = File::create(&path).and_then(|file| {
+            let mut encoder = GzEncoder::new(file, Compression::default());
+            encoder.write_all(content.as_bytes())?;
+            encoder.finish()?.sync_all()
+        });
+        match written {
+            Ok(()) => debug!("Captured {}", path.display()),
+            Err(e) => warn!("Failed to capture {}: {}", path.display(), e),
+        }
+    }
+}
+
+impl<S: FeedSource> FeedSource for Recorder<'_, S> {
+    async fn feed_text(&self) -> Result<String> {
+        let text = self.inner.feed_text().await?;
+        self.save(self.dir.join(FEED_FILE), &text);
+        Ok(text)
+    }
+
+    async fn detail_page(&self, notam_id: &str) -> Result<String> {
+        let html = self.inner.detail_page(notam_id).await?;
+        self.save(
+            self.dir.join(DETAILS_DIR).join(detail_file_name(notam_id)),
+            &html,
+        );
+        Ok(html)
+    }
+}
+
+/// The snapshots in an archive, oldest first. Directories that aren't
+/// named for a time are skipped.
+pub fn snapshots(archive: &Path) -> Result<Vec<(DateTime<Utc>, PathBuf)>> {
+    let entries = fs::read_dir(archive)
+        .with_context(|| format!("Failed to read archive {}", archive.display()))?;
+    let mut found = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        match NaiveDateTime::parse_from_str(&name, SNAPSHOT_FORMAT) {
+            Ok(at) if path.is_dir() => found.push((at.and_utc(), path)),
+            _ => debug!("Skipping {}", path.display()),
+        }
+    }
+    found.sort();
+    Ok(found)
+}
+
+/// Delete snapshots captured more than `keep_days` before `now`, returning
+/// how many went. 0 keeps everything. An archive that doesn't exist yet
+/// has nothing to prune.
+pub fn prune(archive: &Path, keep_days: u32, now: DateTime<Utc>) -> Result<usize> {
+    if keep_days == 0 || !archive.exists() {
+        return Ok(0);
+    }
+    let cutoff = now - Duration::days(keep_days as i64);
+    let mut removed = 0;
+    for (at, dir) in snapshots(archive)? {
+        if at >= cutoff {
+            break;
+        }
+        fs::remove_dir_all(&dir)
+            .with_context(|| format!("Failed to delete capture {}", dir.display()))?;
+        removed += 1;
+    }
+    if removed > 0 {
+        info!("Deleted {removed} capture(s) older than {keep_days} days");
+    }
+    Ok(removed)
+}
+
+/// Check every snapshot in order, from an empty cache, as if it were the
+/// time it was captured. The first snapshot therefore reports every match
+/// as new, whatever the live cache knew then. Detail pages are taken from
+/// the latest snapshot that has them, since a check only downloads the
+/// ones it needs.
+pub async fn replay(
+    archive: &Path,
+    criteria: &MatchCriteria,
+    detail_refresh_hours: u32,
+) -> Result<Vec<(DateTime<Utc>, CheckResult)>> {
+    let snapshots = snapshots(archive)?;
+    let mut store = SqliteStore::in_memory()?;
+    let mut results = Vec::new();
+    for (i, (at, _)) in snapshots.iter().enumerate() {
+        let dirs = snapshots[..=i]
+            .iter()
+            .rev()
+            .map(|(_, dir)| dir.clone())
+            .collect();
+        let criteria = MatchCriteria {
+            as_of: Some(*at),
+            ..criteria.clone()
+        };
+        let source = ReplaySource::layered(dirs);
+        let result =
+            logic::process_feed(&source, &mut store, &criteria, detail_refresh_hours).await?;
+        results.push((*at, result));
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logic::TypeFilter;
+    use crate::store::TfrStore;
+
+    /// A fresh directory under the system temp dir, removed when dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> TempDir {
+            let dir = std::env::temp_dir().join(format!("tfralert-{}-{name}", std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            TempDir(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[tokio::test]
+    async fn captured_checks_replay_the_same() {
+        let temp = TempDir::new("archive");
+        let archive = temp.0.as_path();
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/replay");
+        let criteria = MatchCriteria {
+            types: TypeFilter {
+                include: vec!["SECURITY".to_string()],
+                exclude: Vec::new(),
+            },
+            operating_ceiling_ft: logic::DEFAULT_OPERATING_CEILING_FT,
+            ..Default::default()
+        };
+
+        let mut store = SqliteStore::in_memory().unwrap();
+        let start = "2099-10-01T12:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let mut live = Vec::new();
+        for (i, day) in ["day1", "day2"].iter().enumerate() {
+            let at = start + Duration::days(i as i64);
+            let source = ReplaySource::new(&fixtures.join(day));
+            let recorder = Recorder::new(&source, archive, at).unwrap();
+            let criteria = MatchCriteria {
+                as_of: Some(at),
+                ..criteria.clone()
+            };
+            live.push(
+                logic::process_feed(&recorder, &mut store, &criteria, 0)
+                    .await
+                    .unwrap(),
+            );
+        }
+        // only what was downloaded: HAZARDS are filtered out before their
+        // pages are fetched
+        let day1 = archive.join("20991001T120000.000Z");
+        assert!(day1.join("exportTfrList.json.gz").exists());
+        assert!(day1.join("details/5_1001.html.gz").exists());
+        assert!(!day1.join("details/5_1003.html.gz").exists());
+
+        let replayed = replay(archive, &criteria, 0).await.unwrap();
+        assert_eq!(replayed.len(), 2);
+        assert_eq!(replayed[1].0, start + Duration::days(1));
+        for ((_, again), live) in replayed.iter().zip(&live) {
+            let ids = |r: &CheckResult| {
+                let mut ids: Vec<_> = r.new_matches.iter().map(|e| e.notam_id.clone()).collect();
+                ids.extend(r.changes.iter().map(|c| c.describe()));
+                ids
+            };
+            assert_eq!(ids(again), ids(live));
+        }
+        assert_eq!(store.load_matches().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn old_snapshots_are_pruned() {
+        let temp = TempDir::new("prune");
+        let archive = temp.0.as_path();
+        let now = "2099-10-15T12:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        assert_eq!(prune(archive, 7, now).unwrap(), 0);
+
+        for days_ago in [10, 8, 7, 1] {
+            let at = now - Duration::days(days_ago);
+            fs::create_dir_all(archive.join(at.format(SNAPSHOT_FORMAT).to_string())).unwrap();
+        }
+        fs::create_dir_all(archive.join("notes")).unwrap();
+
+        assert_eq!(prune(archive, 0, now).unwrap(), 0);
+        assert_eq!(prune(archive, 7, now).unwrap(), 2);
+        let left: Vec<_> = snapshots(archive)
+            .unwrap()
+            .into_iter()
+            .map(|(at, _)| (now - at).num_days())
+            .collect();
+        assert_eq!(left, [7, 1]);
+        assert!(archive.join("notes").exists());
+    }
+
+    #[test]
+    fn checks_in_the_same_second_are_kept_apart() {
+        let temp = TempDir::new("same-second");
+        let archive = temp.0.as_path();
+        let source = ReplaySource::new(archive);
+        let at = "2099-10-01T12:00:00.250Z".parse::<DateTime<Utc>>().unwrap();
+        let later = at + Duration::milliseconds(500);
+        Recorder::new(&source, archive, at).unwrap();
+        Recorder::new(&source, archive, later).unwrap();
+        assert!(archive.join("20991001T120000.250Z").is_dir());
+        let found: Vec<_> = snapshots(archive)
+            .unwrap()
+            .into_iter()
+            .map(|(at, _)| at)
+            .collect();
+        assert_eq!(found, [at, later]);
+    }
+}
@@ -0,0 +1,308 @@
+// Copyright 2025. This file is part of TFRAlert.
+
+// TFRAlert is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+// TFRAlert is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License along with TFRAlert. If not, see <https://www.gnu.org/licenses/>.
+
+//! JSON cache files that survive a crash mid-write: each save goes to a
+//! temporary file that replaces the cache in one rename, and the previous
+//! version is kept as `<name>.bak`. A cache that no longer parses is
+//! restored from that backup rather than silently emptied. Files written by
+//! older versions are upgraded by `migrate` as they're read; files we can't
+//! upgrade are left alone.
+
+use crate::migrate::{self, DATA_VERSION};
+use anyhow::{Context, Result};
+use log::{info, warn};
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::{Value, json};
+use std::fs::{self, File};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+/// What `load` found, and what went wrong on the way if anything did
+pub struct Loaded<T> {
+    pub data: Vec<T>,
+    pub warning: Option<String>,
+}
+
+/// Why a cache file's contents couldn't be used
+#[derive(Debug)]
+pub enum CacheError {
+    /// Not JSON, or not laid out like a cache file
+    Damaged(String),
+    /// Written by a newer TFRAlert, or by an older one we failed to upgrade.
+    /// The file may be fine; this version just can't use it.
+    Unsupported(String),
+}
+
+impl std::fmt::Display for CacheError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CacheError::Damaged(reason) | CacheError::Unsupported(reason) => f.write_str(reason),
+        }
+    }
+}
+
+impl std::error::Error for CacheError {}
+
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+fn backup_path(path: &Path) -> PathBuf {
+    sibling(path, ".bak")
+}
+
+/// Cache file contents, upgraded to the current version. Files from before
+/// there was a `{"version": .., "data": [...]}` header are a bare array,
+/// version 0.
+pub fn parse_str<T: DeserializeOwned>(content: &str) -> Result<Vec<T>, CacheError> {
+    let damaged = |reason: &str| CacheError::Damaged(reason.to_string());
+    let json = serde_json::from_str(content).map_err(|e| CacheError::Damaged(e.to_string()))?;
+    let (version, data) = match json {
+        Value::Array(data) => (0, data),
+        Value::Object(mut envelope) => {
+            let version = envelope
+                .get("version")
+                .and_then(Value::as_u64)
+                .ok_or_else(|| damaged("missing version"))?;
+            match envelope.remove("data") {
+                Some(Value::Array(data)) => (version, data),
+                _ => return Err(damaged("missing data")),
+            }
+        }
+        _ => return Err(damaged("not a cache file")),
+    };
+    if version > DATA_VERSION {
+        return Err(CacheError::Unsupported(format!(
+            "written by a newer TFRAlert (version {version})"
+        )));
+    }
+    if version < DATA_VERSION {
+        info!(
+            "Upgrading {} cached item(s) from version {version}",
+            data.len()
+        );
+    }
+    data.into_iter()
+        .map(|mut item| {
+            migrate::upgrade(&mut item, version)
+                .map_err(|e| CacheError::Unsupported(format!("{e:#}")))?;
+            serde_json::from_value(item).map_err(|e| match version {
+                DATA_VERSION => CacheError::Damaged(e.to_string()),
+                _ => CacheError::Unsupported(format!(
+                    "failed to upgrade from version {version}: {e}"
+                )),
+            })
+        })
+        .collect()
+}
+
+/// The cache at `path`, or its backup if the cache is damaged. A missing
+/// file is an empty cache. A file that can't be read at all, or that we
+/// can't upgrade (`CacheError::Unsupported`), is an error and is left as
+/// it is, so a downgrade doesn't throw away what a newer version saved.
+pub fn load<T: DeserializeOwned>(path: &Path) -> Result<Loaded<T>> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            return Ok(Loaded {
+                data: Vec::new(),
+                warning: None,
+            });
+        }
+        Err(e) => {
+            return Err(e).with_context(|| format!("Failed to read cache file {}", path.display()));
+        }
+    };
+    let error = match parse_str(&content) {
+        Ok(data) => {
+            return Ok(Loaded {
+                data,
+                warning: None,
+            });
+        }
+        Err(e @ CacheError::Unsupported(_)) => {
+            return Err(e).with_context(|| format!("Can't use cache file {}", path.display()));
+        }
+        Err(e) => e,
+    };
+
+    let backup = backup_path(path);
+    let from_backup = match fs::read_to_string(&backup) {
+        Ok(content) => parse_str(&content),
+        Err(e) => Err(CacheError::Damaged(e.to_string())),
+    };
+    let (data, warning) = match from_backup {
+        Ok(data) => {
+            if let Err(e) = fs::copy(&backup, path) {
+                warn!("Failed to restore {}: {}", path.display(), e);
+            }
+            let warning = format!(
+                "{} was damaged ({error:#}), restored the previous copy",
+                path.display()
+            );
+            (data, warning)
+        }
+        Err(e @ CacheError::Unsupported(_)) => {
+            return Err(e).with_context(|| {
+                format!("{} is damaged and its backup can't be used", path.display())
+            });
+        }
+        Err(CacheError::Damaged(_)) => {
+            // keep it for a human to look at rather than overwrite it
+            let aside = sibling(path, ".corrupt");
+            let _ = fs::rename(path, &aside);
+            let warning = format!(
+                "{} was damaged ({error:#}) with no usable backup, moved it to {} and started over",
+                path.display(),
+                aside.display()
+            );
+            (Vec::new(), warning)
+        }
+    };
+    warn!("{warning}");
+    Ok(Loaded {
+        data,
+        warning: Some(warning),
+    })
+}
+
+/// Replace the cache at `path`, keeping the old one as the backup
+pub fn save<T: Serialize>(path: &Path, data: &[T]) -> Result<()> {
+    let serialized = serde_json::to_string_pretty(&json!({
+        "version": DATA_VERSION,
+        "data": data,
+    }))?;
+
+    let tmp = sibling(path, ".tmp");
+    let mut file = File::create(&tmp)
+        .with_context(|| format!("Failed to write cache file {}", tmp.display()))?;
+    file.write_all(serialized.as_bytes())?;
+    file.sync_all()?;
+    drop(file);
+
+    if path.exists() {
+        fs::copy(path, backup_path(path))
+            .with_context(|| format!("Failed to back up {}", path.display()))?;
+    }
+    fs::rename(&tmp, path)
+        .with_context(|| format!("Failed to write cache file {}", path.display()))?;
+
+    // make the rename itself durable
+    #[cfg(unix)]
+    if let Some(dir) = path.parent().and_then(|d| File::open(d).ok()) {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logic::ParsedTFREvent;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("tfralert-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn save_keeps_previous_as_backup() {
+        let path = temp_dir("backup").join("cache.json");
+        save(&path, &["a"]).unwrap();
+        save(&path, &["b"]).unwrap();
+
+        let loaded: Loaded<String> = load(&path).unwrap();
+        assert_eq!(loaded.data, ["b"]);
+        assert!(loaded.warning.is_none());
+        let backup: Loaded<String> = load(&backup_path(&path)).unwrap();
+        assert_eq!(backup.data, ["a"]);
+    }
+
+    #[test]
+    fn damaged_cache_is_restored_from_backup() {
+        let path = temp_dir("restore").join("cache.json");
+        save(&path, &["a"]).unwrap();
+        save(&path, &["b"]).unwrap();
+        fs::write(&path, "[\"trunc").unwrap();
+
+        let loaded: Loaded<String> = load(&path).unwrap();
+        assert_eq!(loaded.data, ["a"]);
+        assert!(loaded.warning.is_some());
+        // and the file itself is good again
+        let again: Loaded<String> = load(&path).unwrap();
+        assert_eq!(again.data, ["a"]);
+        assert!(again.warning.is_none());
+    }
+
+    #[test]
+    fn reads_files_without_a_header() {
+        let path = temp_dir("bare").join("cache.json");
+        fs::write(&path, "[\"a\"]").unwrap();
+        let loaded: Loaded<String> = load(&path).unwrap();
+        assert_eq!(loaded.data, ["a"]);
+    }
+
+    #[test]
+    fn unreadable_cache_is_an_error() {
+        // a directory in its place can't be read, even by root
+        let path = temp_dir("unreadable").join("cache.json");
+        fs::create_dir(&path).unwrap();
+        fs::write(backup_path(&path), "[\"a\"]").unwrap();
+
+        assert!(load::<String>(&path).is_err());
+        assert!(path.is_dir());
+        assert!(!sibling(&path, ".corrupt").exists());
+    }
+
+    #[test]
+    fn missing_cache_is_empty() {
+        let loaded: Loaded<String> = load(&temp_dir("missing").join("cache.json")).unwrap();
+        assert!(loaded.data.is_empty());
+        assert!(loaded.warning.is_none());
+    }
+
+    #[test]
+    fn newer_cache_is_left_alone() {
+        let newer = include_str!("../tests/fixtures/cache/v99_matches.json");
+        let path = temp_dir("newer").join("cache.json");
+        fs::write(&path, newer).unwrap();
+        fs::write(backup_path(&path), newer).unwrap();
+
+        let Err(error) = load::<ParsedTFREvent>(&path) else {
+            panic!("loaded a cache from a newer version");
+        };
+        assert!(matches!(
+            error.downcast_ref::<CacheError>(),
+            Some(CacheError::Unsupported(_))
+        ));
+        assert!(format!("{error:#}").contains("newer TFRAlert (version 99)"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), newer);
+        assert!(!sibling(&path, ".corrupt").exists());
+
+        // nor is a damaged cache whose backup is newer
+        fs::write(&path, "[\"trunc").unwrap();
+        assert!(load::<ParsedTFREvent>(&path).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "[\"trunc");
+        assert_eq!(fs::read_to_string(backup_path(&path)).unwrap(), newer);
+        assert!(!sibling(&path, ".corrupt").exists());
+    }
+
+    #[test]
+    fn failed_upgrade_is_not_damage() {
+        // version 0, but nothing an upgrade can turn into a string
+        let result = parse_str::<String>("[{\"notam_id\": \"5/1\"}]");
+        assert!(matches!(result, Err(CacheError::Unsupported(_))));
+        let result =
+            parse_str::<String>(&format!("{{\"version\": {DATA_VERSION}, \"data\": [1]}}"));
+        assert!(matches!(result, Err(CacheError::Damaged(_))));
+    }
+}
@@ -0,0 +1,412 @@
+// Copyright 2025. This file is part of TFRAlert.
+
+// TFRAlert is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+// TFRAlert is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License along with TFRAlert. If not, see <https://www.gnu.org/licenses/>.
+
+//! `tfralert <command>`. Exit codes: 0 ok, 1 error, 2 bad arguments (from
+//! clap), 3 NOTAM not found (`show`), 10 new matches (`check`).
+
+use crate::archive;
+use crate::config::Config;
+use crate::lifecycle::LifecycleChange;
+use crate::logic::{self, CheckResult, ParsedTFREvent};
+use crate::store::{self, TfrStore};
+use crate::{daemon, detail_url};
+use anyhow::{Context, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+pub const EXIT_OK: i32 = 0;
+pub const EXIT_ERROR: i32 = 1;
+pub const EXIT_NEW_MATCHES: i32 = 10;
+pub const EXIT_NOT_FOUND: i32 = 3;
+
+/// Collect and filter TFRs/NOTAMs. Opens the desktop app without a command.
+#[derive(Debug, Parser)]
+#[command(name = "tfralert", version)]
+pub struct Cli {
+    /// Print JSON instead of text
+    #[arg(long, global = true)]
+    pub json: bool,
+
+    /// Where caches are kept, instead of the platform data directory
+    #[arg(long, global = true, env = "TFRALERT_DATA_DIR")]
+    pub data_dir: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Check the feed on a schedule without a window, until SIGTERM or Ctrl-C
+    Daemon,
+    /// Check the feed once and print new matches; exits 10 if there are any
+    Check,
+    /// Print matches saved by earlier checks, newest first
+    List,
+    /// Print everything we know about one NOTAM, e.g. `show 5/1234`
+    Show { notam_id: String },
+    /// Write saved matches to a file, or stdout
+    Export {
+        #[arg(long, value_enum, default_value_t = ExportFormat::Json)]
+        format: ExportFormat,
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Check every snapshot in a capture archive (see `capture_dir`) again,
+    /// from an empty cache, and print what each check finds (the first
+    /// reports every match as new)
+    Replay { archive: PathBuf },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    Json,
+    Csv,
+}
+
+/// Exit code for what `run` returned, logging an error
+pub fn exit_code(outcome: Result<i32>) -> i32 {
+    match outcome {
+        Ok(code) => code,
+        Err(e) => {
+            log::error!("{e:#}");
+            EXIT_ERROR
+        }
+    }
+}
+
+/// Run a command, returning the process exit code
+pub fn run(command: Command, json: bool, config: Config) -> Result<i32> {
+    let open_store = || store::open(config.storage);
+    match command {
+        Command::Daemon => daemon::run(&config).map(|_| EXIT_OK),
+        Command::Check => check(open_store()?.as_mut(), &config, json),
+        Command::List => {
+            print_events(&open_store()?.load_matches()?, json)?;
+            Ok(EXIT_OK)
+        }
+        Command::Show { notam_id } => show(open_store()?.as_ref(), &notam_id, json),
+        Command::Export { format, output } => {
+            export(&open_store()?.load_matches()?, format, output)?;
+            Ok(EXIT_OK)
+        }
+        Command::Replay { archive } => replay(&archive, &config, json).map(|_| EXIT_OK),
+    }
+}
+
+fn check(store: &mut dyn TfrStore, config: &Config, json: bool) -> Result<i32> {
+    let runtime = tokio::runtime::Runtime::new().context("Failed to start async runtime")?;
+    let checked = runtime.block_on(logic::check_feed(store, config))?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&checked_json(&checked))?);
+    } else if checked.new_matches.is_empty() {
+        println!("No new matches ({})", config.criteria().describe());
+        print_changes(&checked.changes);
+    } else {
+        print_checked(&checked)?;
+    }
+    Ok(check_exit_code(&checked))
+}
+
+fn check_exit_code(checked: &CheckResult) -> i32 {
+    if checked.new_matches.is_empty() {
+        EXIT_OK
+    } else {
+        EXIT_NEW_MATCHES
+    }
+}
+
+/// `{"new_matches": [...], "changes": [...]}`
+fn checked_json(checked: &CheckResult) -> serde_json::Value {
+    serde_json::json!({
+        "new_matches": checked.new_matches,
+        "changes": checked.changes,
+    })
+}
+
+fn print_changes(changes: &[LifecycleChange]) {
+    for change in changes {
+        println!("{}", change.describe());
+        for field in &change.changes {
+            println!("  {field}");
+        }
+    }
+}
+
+fn print_checked(checked: &CheckResult) -> Result<()> {
+    if !checked.new_matches.is_empty() {
+        println!("{} new match(es):", checked.new_matches.len());
+        print_events(&checked.new_matches, false)?;
+    }
+    print_changes(&checked.changes);
+    Ok(())
+}
+
+fn replay(archive: &Path, config: &Config, json: bool) -> Result<()> {
+    let runtime = tokio::runtime::Runtime::new().context("Failed to start async runtime")?;
+    let replayed = runtime.block_on(archive::replay(
+        archive,
+        &config.criteria(),
+        config.detail_refresh_hours,
+    ))?;
+    if replayed.is_empty() {
+        eprintln!("No captured checks in {}", archive.display());
+    }
+
+    if json {
+        let checks: Vec<_> = replayed
+            .iter()
+            .map(|(at, checked)| {
+                let mut check = checked_json(checked);
+                check["at"] = serde_json::json!(at);
+                check
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&checks)?);
+        return Ok(());
+    }
+    for (at, checked) in &replayed {
+        println!("== {}", at.format("%m/%d/%Y %H:%M UTC"));
+        print_checked(checked)?;
+    }
+    Ok(())
+}
+
+fn show(store: &dyn TfrStore, notam_id: &str, json: bool) -> Result<i32> {
+    let Some(event) = store.find_event(notam_id.trim())? else {
+        eprintln!("No cached details for NOTAM {notam_id}");
+        return Ok(EXIT_NOT_FOUND);
+    };
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&event)?);
+        return Ok(EXIT_OK);
+    }
+
+    let field = |label: &str, value: &str| {
+        if !value.is_empty() {
+            println!("{:<14}{}", format!("{label}:"), value);
+        }
+    };
+    println!("NOTAM {}", event.notam_id);
+    field("Type", &event.r#type);
+    field("Location", &event.location);
+    field("Issued", &event.issue_date);
+    field("Begins", &event.begin);
+    field("Ends", &event.end);
+    field("Reason", &event.reason);
+    field("Replaces", &event.replaced);
+    field("Supersedes", &event.supersedes.join(" → "));
+    field("Replaced by", event.superseded_by.as_deref().unwrap_or(""));
+    for change in &event.field_changes {
+        field("Changed", &change.to_string());
+    }
+    for (i, area) in event.airspace.areas.iter().enumerate() {
+        let place = if area.boundary.is_empty() {
+            format!("{} {}", area.center, area.radius)
+        } else {
+            area.boundary.clone()
+        };
+        field(
+            &format!("Area {}", i + 1),
+            &format!("{}, {}", place.trim(), area.altitude),
+        );
+    }
+    for effective in &event.airspace.effective {
+        field("Effective", effective);
+    }
+    field("Restrictions", &event.restrictions);
+    field("Other info", &event.other_info);
+    field(
+        "NOTAM text",
+        &event
+            .notam_text
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" "),
+    );
+    field("Description", &event.description);
+    field("Matched", &event.match_reasons.join(", "));
+    if let Some(quality) = event.parse_quality
+        && !event.parse_warnings.is_empty()
+    {
+        field("Parsed", &format!("{quality}% of the detail page"));
+        for warning in &event.parse_warnings {
+            println!("{:<14}  {warning}", "");
+        }
+    }
+    if let Some(sent_at) = store.last_notified(&event.notam_id)? {
+        field(
+            "Notified",
+            &sent_at.format("%m/%d/%Y %H:%M UTC").to_string(),
+        );
+    }
+    for change in store.lifecycle_history(&event.notam_id)? {
+        let at = change.at.format("%m/%d/%Y %H:%M UTC");
+        let detail = if change.detail.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", change.detail)
+        };
+        field("History", &format!("{at} {}{detail}", change.state));
+        for field_change in &change.changes {
+            println!("{:<14}  {field_change}", "");
+        }
+    }
+    field("Details", &detail_url(&event.notam_id));
+    Ok(EXIT_OK)
+}
+
+fn print_events(events: &[ParsedTFREvent], json: bool) -> Result<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(events)?);
+        return Ok(());
+    }
+    for e in events {
+        println!("{:<10} {:<20} {}", e.notam_id, e.issue_date, e.location);
+        if !e.match_reasons.is_empty() {
+            println!("           {}", e.match_reasons.join(", "));
+        }
+    }
+    Ok(())
+}
+
+const CSV_HEADER: &[&str] = &[
+    "notam_id",
+    "type",
+    "location",
+    "issue_date",
+    "begin",
+    "end",
+    "reason",
+    "match_reasons",
+    "url",
+];
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn to_csv(events: &[ParsedTFREvent]) -> String {
+    let mut out = CSV_HEADER.join(",");
+    out.push('\n');
+    for e in events {
+        let row = [
+            e.notam_id.clone(),
+            e.r#type.clone(),
+            e.location.clone(),
+            e.issue_date.clone(),
+            e.begin.clone(),
+            e.end.clone(),
+            e.reason.clone(),
+            e.match_reasons.join("; "),
+            detail_url(&e.notam_id),
+        ];
+        let row: Vec<_> = row.iter().map(|v| csv_field(v)).collect();
+        out.push_str(&row.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+fn export(events: &[ParsedTFREvent], format: ExportFormat, output: Option<PathBuf>) -> Result<()> {
+    let content = match format {
+        ExportFormat::Json => serde_json::to_string_pretty(events)? + "\n",
+        ExportFormat::Csv => to_csv(events),
+    };
+    match output {
+        Some(path) => {
+            fs::write(&path, content)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            eprintln!("Exported {} match(es) to {}", events.len(), path.display());
+        }
+        None => std::io::stdout().write_all(content.as_bytes())?,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lifecycle::LifecycleState;
+    use chrono::Utc;
+
+    fn event(notam_id: &str, location: &str, reason: &str) -> ParsedTFREvent {
+        ParsedTFREvent {
+            notam_id: notam_id.to_string(),
+            location: location.to_string(),
+            reason: reason.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn csv_quoting() {
+        assert_eq!(csv_field("ANNAPOLIS"), "ANNAPOLIS");
+        assert_eq!(csv_field("ANNAPOLIS, MD"), "\"ANNAPOLIS, MD\"");
+        assert_eq!(csv_field("the \"Big\" game"), "\"the \"\"Big\"\" game\"");
+        assert_eq!(csv_field("line one\nline two"), "\"line one\nline two\"");
+        assert_eq!(csv_field(""), "");
+    }
+
+    #[test]
+    fn csv_rows() {
+        let mut e = event("5/1234", "ANNAPOLIS, MD", "VIP \"movement\"\nsee NOTAM");
+        e.match_reasons = vec!["type SECURITY".to_string(), "keyword stadium".to_string()];
+        let csv = to_csv(&[e]);
+        let (header, row) = csv.split_once('\n').unwrap();
+        assert_eq!(header, CSV_HEADER.join(","));
+        assert!(
+            row.starts_with("5/1234,,\"ANNAPOLIS, MD\",,,,\"VIP \"\"movement\"\"\nsee NOTAM\",")
+        );
+        assert!(row.contains(",type SECURITY; keyword stadium,"));
+        assert!(row.ends_with(&format!("{}\n", detail_url("5/1234"))));
+        assert_eq!(to_csv(&[]), CSV_HEADER.join(",") + "\n");
+    }
+
+    #[test]
+    fn exit_codes() {
+        let mut checked = CheckResult::default();
+        assert_eq!(check_exit_code(&checked), EXIT_OK);
+        checked.new_matches.push(event("5/1234", "", ""));
+        assert_eq!(check_exit_code(&checked), EXIT_NEW_MATCHES);
+
+        assert_eq!(exit_code(Ok(EXIT_OK)), 0);
+        assert_eq!(exit_code(Err(anyhow::anyhow!("no network"))), 1);
+        assert_eq!(exit_code(Ok(EXIT_NOT_FOUND)), 3);
+        assert_eq!(exit_code(Ok(EXIT_NEW_MATCHES)), 10);
+
+        let store = store::SqliteStore::in_memory().unwrap();
+        assert_eq!(show(&store, "5/1234", true).unwrap(), EXIT_NOT_FOUND);
+    }
+
+    #[test]
+    fn check_json_has_changes() {
+        let checked = CheckResult {
+            new_matches: vec![event("5/1234", "ANNAPOLIS, MD", "")],
+            changes: vec![LifecycleChange {
+                notam_id: "4/0001".to_string(),
+                state: LifecycleState::Cancelled,
+                at: Utc::now(),
+                location: String::new(),
+                detail: String::new(),
+                changes: Vec::new(),
+            }],
+        };
+        let json = checked_json(&checked);
+        assert_eq!(json["new_matches"][0]["notam_id"], "5/1234");
+        assert_eq!(json["changes"][0]["notam_id"], "4/0001");
+    }
+}
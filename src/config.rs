@@ -0,0 +1,329 @@
+// Copyright 2025. This file is part of TFRAlert.
+
+// TFRAlert is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+// TFRAlert is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License along with TFRAlert. If not, see <https://www.gnu.org/licenses/>.
+
+use crate::geo::WatchLocation;
+use crate::keywords::{self, KeywordRules};
+use crate::logic::{self, DEFAULT_OPERATING_CEILING_FT, MatchCriteria, TypeFilter};
+use crate::source::DEFAULT_BASE_URL;
+use crate::store::StorageKind;
+use anyhow::{Context, Result, bail};
+use log::{debug, error, info};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::PathBuf};
+
+const CONFIG_DIR: &str = "tfralert";
+const CONFIG_FILE: &str = "config.toml";
+const MIN_REFRESH_SECONDS: u64 = 60;
+
+/// User settings, read from `config.toml` in the platform config directory.
+/// Every field is optional in the file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub refresh_seconds: u64,
+    /// TFR types to alert on, e.g. `types = ["SECURITY", "VIP"]`, or
+    /// `[types]` with `include`/`exclude` lists
+    pub types: TypeFilter,
+    /// Two-letter states to alert on, e.g. `states = ["MD", "DC"]`
+    pub states: TypeFilter,
+    /// Issuing facilities (ARTCCs) to alert on, e.g. `facilities = ["ZDC"]`
+    pub facilities: TypeFilter,
+    pub keywords: Vec<String>,
+    /// More keywords, one per line
+    pub keyword_file: Option<PathBuf>,
+    /// How high we fly, feet AGL
+    pub operating_ceiling_ft: u32,
+    /// Ground elevation (feet MSL) used to compare MSL and FL limits
+    pub ground_elevation_ft: u32,
+    /// Only alert on TFRs active within this many hours; 0 for any time
+    pub lookahead_hours: u32,
+    /// Download details of known TFRs again after this many hours, to catch
+    /// edits the feed doesn't show; 0 for never
+    pub detail_refresh_hours: u32,
+    pub locations: Vec<WatchLocation>,
+    pub notifications: NotificationConfig,
+    /// `"sqlite"`, or `"json"` for the older cache files
+    pub storage: StorageKind,
+    /// Where the TFR API lives, for a mirror or proxy
+    pub faa_base_url: String,
+    /// Read the feed and detail pages recorded in this directory instead of
+    /// downloading them, for demos and offline testing
+    pub feed_dir: Option<PathBuf>,
+    /// Keep a compressed copy of everything downloaded here, one directory
+    /// per check, for `tfralert replay`
+    pub capture_dir: Option<PathBuf>,
+    /// Delete captures older than this many days; 0 to keep them all
+    pub capture_keep_days: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotificationConfig {
+    pub enabled: bool,
+    /// More new TFRs than this are summarized in one notification
+    pub summarize_after: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            refresh_seconds: 600,
+            types: TypeFilter {
+                include: vec!["SECURITY".to_string()],
+                exclude: Vec::new(),
+            },
+            states: TypeFilter::default(),
+            facilities: TypeFilter::default(),
+            keywords: Vec::new(),
+            keyword_file: None,
+            operating_ceiling_ft: DEFAULT_OPERATING_CEILING_FT,
+            ground_elevation_ft: 0,
+            lookahead_hours: 72,
+            detail_refresh_hours: 24,
+            locations: Vec::new(),
+            notifications: NotificationConfig::default(),
+            storage: StorageKind::default(),
+            faa_base_url: DEFAULT_BASE_URL.to_string(),
+            feed_dir: None,
+            capture_dir: None,
+            capture_keep_days: 14,
+        }
+    }
+}
+
+impl Default for NotificationConfig {
+    fn default() -> Self {
+        NotificationConfig {
+            enabled: true,
+            summarize_after: 1,
+        }
+    }
+}
+
+/// `<config dir>/tfralert/config.toml`
+pub fn config_path() -> Result<PathBuf> {
+    let dir = dirs::config_dir().context("No config directory on this platform")?;
+    Ok(dir.join(CONFIG_DIR).join(CONFIG_FILE))
+}
+
+impl Config {
+    /// Config from the default location, or defaults if there is no file yet
+    pub fn load() -> Result<Config> {
+        let path = config_path()?;
+        if !path.exists() {
+            info!("No config at {}, using defaults", path.display());
+            return Ok(Config::default());
+        }
+        Config::load_from(&path)
+    }
+
+    pub fn load_from(path: &PathBuf) -> Result<Config> {
+        debug!("Load config from {}", path.display());
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        let config: Config = toml::from_str(&content)
+            .with_context(|| format!("Invalid config file {}", path.display()))?;
+        config
+            .validate()
+            .with_context(|| format!("Invalid config file {}", path.display()))?;
+        Ok(config)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        self.validate()?;
+        let path = config_path()?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, toml::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write config file {}", path.display()))?;
+        info!("Saved config to {}", path.display());
+        Ok(())
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.refresh_seconds < MIN_REFRESH_SECONDS {
+            bail!(
+                "refresh_seconds must be at least {MIN_REFRESH_SECONDS}, got {}",
+                self.refresh_seconds
+            );
+        }
+        let filters = [
+            ("types", &self.types),
+            ("states", &self.states),
+            ("facilities", &self.facilities),
+        ];
+        for (name, filter) in filters {
+            if [&filter.include, &filter.exclude]
+                .iter()
+                .any(|l| l.iter().any(|t| t.trim().is_empty()))
+            {
+                bail!("{name} must not contain empty entries");
+            }
+            if let Some(t) = filter.include.iter().find(|t| {
+                filter
+                    .exclude
+                    .iter()
+                    .any(|x| x.trim().eq_ignore_ascii_case(t.trim()))
+            }) {
+                bail!("{name}: '{t}' is both included and excluded");
+            }
+        }
+        if let Some(path) = &self.keyword_file
+            && !path.exists()
+        {
+            bail!("keyword_file {} does not exist", path.display());
+        }
+        if let Some(dir) = &self.feed_dir
+            && !dir.is_dir()
+        {
+            bail!("feed_dir {} is not a directory", dir.display());
+        }
+        KeywordRules::parse(&self.keyword_lines())?;
+        for loc in &self.locations {
+            if loc.name.trim().is_empty() {
+                bail!("every location needs a name");
+            }
+            if !(-90.0..=90.0).contains(&loc.lat) || !(-180.0..=180.0).contains(&loc.lon) {
+                bail!(
+                    "location '{}' has out-of-range coordinates ({}, {})",
+                    loc.name,
+                    loc.lat,
+                    loc.lon
+                );
+            }
+            if loc.radius_nm.is_nan() || loc.radius_nm < 0.0 {
+                bail!(
+                    "location '{}' needs a non-negative radius_nm, got {}",
+                    loc.name,
+                    loc.radius_nm
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Keyword rule lines from the config and `keyword_file`
+    fn keyword_lines(&self) -> Vec<String> {
+        let mut lines = self.keywords.clone();
+        lines.extend(logic::load_keywords(
+            self.keyword_file.as_ref().and_then(|p| p.to_str()),
+        ));
+        lines
+    }
+
+    /// Matching rules, with keywords from the config and `keyword_file`.
+    /// Rules that don't parse (the file may have changed since startup) are
+    /// logged and skipped.
+    pub fn criteria(&self) -> MatchCriteria {
+        let valid: Vec<String> = self
+            .keyword_lines()
+            .into_iter()
+            .filter(|line| match keywords::parse_line(line) {
+                Ok(_) => true,
+                Err(e) => {
+                    error!("Skipping {}", e);
+                    false
+                }
+            })
+            .collect();
+        let keywords = KeywordRules::parse(&valid).unwrap_or_default();
+
+        MatchCriteria {
+            types: self.types.clone(),
+            states: self.states.clone(),
+            facilities: self.facilities.clone(),
+            keywords,
+            locations: self.locations.clone(),
+            operating_ceiling_ft: self.operating_ceiling_ft,
+            ground_elevation_ft: self.ground_elevation_ft,
+            lookahead_hours: self.lookahead_hours,
+            as_of: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Result<Config> {
+        let config: Config = toml::from_str(text)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn location(lat: f64, lon: f64, radius: &str) -> String {
+        format!(
+            "[[locations]]\nname = \"Field\"\nlat = {lat:?}\nlon = {lon:?}\nradius_nm = {radius}\n"
+        )
+    }
+
+    #[test]
+    fn defaults_for_missing_fields() {
+        let config = parse("refresh_seconds = 300").unwrap();
+        assert_eq!(config.refresh_seconds, 300);
+        assert_eq!(
+            config.operating_ceiling_ft,
+            Config::default().operating_ceiling_ft
+        );
+        assert_eq!(parse("").unwrap(), Config::default());
+    }
+
+    #[test]
+    fn misspelled_key_is_rejected() {
+        assert!(parse("refresh_second = 300").is_err());
+        assert!(parse("[notifications]\nenable = false").is_err());
+    }
+
+    #[test]
+    fn refresh_has_a_floor() {
+        assert!(parse("refresh_seconds = 59").is_err());
+        assert!(parse("refresh_seconds = 60").is_ok());
+    }
+
+    #[test]
+    fn empty_type_entries_are_rejected() {
+        assert!(parse("types = [\"SECURITY\", \" \"]").is_err());
+        assert!(parse("types = [\"\"]").is_err());
+    }
+
+    #[test]
+    fn type_list_or_table() {
+        let short = parse("types = [\"SECURITY\"]").unwrap();
+        assert_eq!(short.types.include, ["SECURITY"]);
+        assert!(short.types.exclude.is_empty());
+
+        let table = parse("[types]\ninclude = [\"SECURITY\"]\nexclude = [\"VIP\"]").unwrap();
+        assert_eq!(table.types.include, ["SECURITY"]);
+        assert_eq!(table.types.exclude, ["VIP"]);
+
+        let exclude_only = parse("[types]\nexclude = [\"HAZARDS\"]").unwrap();
+        assert!(exclude_only.types.include.is_empty());
+        assert!(parse("[types]\ninclud = [\"SECURITY\"]").is_err());
+    }
+
+    #[test]
+    fn type_in_both_lists_is_rejected() {
+        assert!(parse("[types]\ninclude = [\"SECURITY\"]\nexclude = [\" security \"]").is_err());
+        assert!(parse("[types]\nexclude = [\"\"]").is_err());
+    }
+
+    #[test]
+    fn location_ranges() {
+        assert!(parse(&location(38.5, -77.0, "10")).is_ok());
+        assert!(parse(&location(38.5, -77.0, "0")).is_ok());
+        assert!(parse(&location(91.0, -77.0, "10")).is_err());
+        assert!(parse(&location(38.5, -181.0, "10")).is_err());
+        assert!(parse(&location(38.5, -77.0, "-1")).is_err());
+        assert!(parse(&location(38.5, -77.0, "nan")).is_err());
+        assert!(
+            parse("[[locations]]\nname = \" \"\nlat = 0.0\nlon = 0.0\nradius_nm = 1.0\n").is_err()
+        );
+    }
+}
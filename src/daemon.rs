@@ -0,0 +1,141 @@
+// Copyright 2025. This file is part of TFRAlert.
+
+// TFRAlert is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+// TFRAlert is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License along with TFRAlert. If not, see <https://www.gnu.org/licenses/>.
+
+//! Headless mode: check the feed every `refresh_seconds` without a window,
+//! for servers and Raspberry Pis at the field.
+
+use crate::config::Config;
+use crate::store::{self, TfrStore};
+use crate::{logic, notify, paths};
+use anyhow::{Context, Result, bail};
+use log::{error, info, warn};
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+
+const LOCK_FILE: &str = "tfralert.lock";
+
+/// Run until SIGTERM or Ctrl-C. Only one daemon may run per data directory.
+pub fn run(config: &Config) -> Result<()> {
+    let _lock = lock_instance(&paths::data_file(LOCK_FILE))?;
+    let mut store = store::open(config.storage)?;
+    info!(
+        "TFRAlert daemon started, checking every {} s ({})",
+        config.refresh_seconds,
+        config.criteria().describe()
+    );
+
+    let runtime = tokio::runtime::Runtime::new().context("Failed to start async runtime")?;
+    runtime.block_on(run_loop(store.as_mut(), config));
+
+    info!("TFRAlert daemon stopped");
+    Ok(())
+}
+
+async fn run_loop(store: &mut dyn TfrStore, config: &Config) {
+    // listens from the start, so a signal during a check isn't lost
+    let mut shutdown = tokio::spawn(shutdown_signal());
+
+    loop {
+        // a check always runs to the end so the caches are never half written
+        check_once(store, config).await;
+        if shutdown.is_finished() {
+            break;
+        }
+        tokio::select! {
+            _ = &mut shutdown => break,
+            _ = tokio::time::sleep(Duration::from_secs(config.refresh_seconds)) => {}
+        }
+    }
+}
+
+async fn check_once(store: &mut dyn TfrStore, config: &Config) {
+    let result = match logic::refresh_tfr_results(store, config).await {
+        Ok(result) => result,
+        Err(e) => {
+            error!("Check failed: {e:#}");
+            return;
+        }
+    };
+
+    let new_events = result.new_events();
+    for event in &new_events {
+        info!(
+            "New match {} ({}): {}",
+            event.notam_id,
+            event.location,
+            event.match_reasons.join(", ")
+        );
+    }
+    for change in &result.changes {
+        info!("{}", change.describe());
+    }
+    info!(
+        "{} matching TFRs, {} new",
+        result.events.len(),
+        new_events.len()
+    );
+    if !new_events.is_empty() && config.notifications.enabled {
+        notify::notify(&new_events, &config.notifications);
+        let ids: Vec<_> = new_events.iter().map(|e| e.notam_id.clone()).collect();
+        if let Err(e) = store.record_notifications(&ids) {
+            error!("Failed to record notifications: {e:#}");
+        }
+    }
+    notify::notify_changes(&result.changes, &config.notifications);
+}
+
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+        match signal(SignalKind::terminate()) {
+            Ok(mut term) => {
+                tokio::select! {
+                    _ = term.recv() => info!("SIGTERM received, shutting down"),
+                    _ = tokio::signal::ctrl_c() => info!("Interrupted, shutting down"),
+                }
+                return;
+            }
+            Err(e) => warn!("Can't listen for SIGTERM: {e}"),
+        }
+    }
+    if let Err(e) = tokio::signal::ctrl_c().await {
+        // without any signal to wait for, run until killed
+        warn!("Can't listen for Ctrl-C: {e}");
+        std::future::pending::<()>().await;
+    }
+    info!("Interrupted, shutting down");
+}
+
+/// Held for as long as the daemon runs. The OS releases it if we die, so a
+/// stale lock file never blocks a restart.
+fn lock_instance(path: &Path) -> Result<File> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)
+        .with_context(|| format!("Failed to open lock file {}", path.display()))?;
+    match file.try_lock() {
+        Ok(()) => {}
+        Err(TryLockError::WouldBlock) => bail!(
+            "Another TFRAlert daemon is already running (lock {} is held)",
+            path.display()
+        ),
+        Err(TryLockError::Error(e)) => {
+            return Err(e).with_context(|| format!("Failed to lock {}", path.display()));
+        }
+    }
+
+    // the pid is only for humans
+    file.set_len(0)?;
+    writeln!(file, "{}", std::process::id())?;
+    Ok(file)
+}
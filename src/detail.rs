@@ -0,0 +1,653 @@
+// Copyright 2025. This file is part of TFRAlert.
+
+// TFRAlert is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+// TFRAlert is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License along with TFRAlert. If not, see <https://www.gnu.org/licenses/>.
+
+//! NOTAM detail pages. Rows are picked by their label cell rather than by
+//! words anywhere in the row, and whatever can't be read becomes a warning
+//! on the event instead of a panic or a silently empty field. The NOTAM
+//! text on the page fills gaps in the tables and is checked against them.
+
+use crate::altitude;
+use crate::geo::{self, Circle, Shape};
+use crate::logic::{AirspaceArea, ParsedTFREvent};
+use crate::notam_text::NotamText;
+use crate::schedule;
+use chrono::{DateTime, Utc};
+use scraper::{ElementRef, Html, Selector};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ParseIssue {
+    /// Not on the page
+    Missing,
+    /// Given more than once, differently; the first one is kept
+    Ambiguous,
+    /// On the page, but not in a form we understand
+    Unparsed,
+    /// Not in the tables, but taken from the NOTAM text
+    Recovered,
+}
+
+impl fmt::Display for ParseIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ParseIssue::Missing => "missing",
+            ParseIssue::Ambiguous => "ambiguous",
+            ParseIssue::Unparsed => "unparsed",
+            ParseIssue::Recovered => "taken from the NOTAM text",
+        })
+    }
+}
+
+/// Something about one field of a detail page that didn't come out right
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParseWarning {
+    pub field: String,
+    pub issue: ParseIssue,
+    #[serde(default)]
+    pub detail: String,
+}
+
+impl fmt::Display for ParseWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.field, self.issue)?;
+        if !self.detail.is_empty() {
+            write!(f, ": {}", self.detail)?;
+        }
+        Ok(())
+    }
+}
+
+/// Metadata rows by the start of their label, lowercased
+const LABELS: &[(&str, &str)] = &[
+    ("notam number", "notam_id"),
+    ("issue date", "issue_date"),
+    ("location", "location"),
+    ("beginning date", "begin"),
+    ("ending date", "end"),
+    ("reason", "reason"),
+    ("type", "type"),
+    ("replaced notam", "replaced"),
+];
+
+/// What a complete page gives us; the quality score is the share of these
+/// that came out without a warning
+const EXPECTED_FIELDS: &[&str] = &[
+    "notam_id",
+    "issue_date",
+    "location",
+    "begin",
+    "end",
+    "reason",
+    "type",
+    "airspace",
+    "altitude",
+    "schedule",
+];
+
+fn selector(css: &str) -> Selector {
+    Selector::parse(css).expect("valid selector")
+}
+
+fn extract_text(element: &ElementRef) -> String {
+    element
+        .text()
+        .map(|t| t.trim().replace("\\r", "").replace("\\n", ""))
+        .filter(|t| !t.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// "Issue Date :" → "issue date"
+fn normalize_label(text: &str) -> String {
+    text.trim().trim_end_matches(':').trim().to_lowercase()
+}
+
+fn text_field<'a>(event: &'a mut ParsedTFREvent, field: &str) -> Option<&'a mut String> {
+    Some(match field {
+        "notam_id" => &mut event.notam_id,
+        "issue_date" => &mut event.issue_date,
+        "location" => &mut event.location,
+        "begin" => &mut event.begin,
+        "end" => &mut event.end,
+        "reason" => &mut event.reason,
+        "type" => &mut event.r#type,
+        "replaced" => &mut event.replaced,
+        _ => return None,
+    })
+}
+
+/// Tables whose text mentions `heading`, leaving out layout tables that
+/// only wrap another one that does
+fn innermost_tables<'a>(document: &'a Html, heading: &str) -> Vec<ElementRef<'a>> {
+    let tables = selector("table");
+    document
+        .select(&tables)
+        .filter(|t| extract_text(t).contains(heading))
+        .filter(|t| {
+            !t.select(&tables)
+                .any(|inner| extract_text(&inner).contains(heading))
+        })
+        .collect()
+}
+
+/// Rows of `root` as (label, value) cells, skipping rows that only hold
+/// other tables. A row with one cell is its own label and value.
+fn rows<'a>(root: ElementRef<'a>) -> Vec<(ElementRef<'a>, ElementRef<'a>)> {
+    let (tr, td, table) = (selector("tr"), selector("td"), selector("table"));
+    root.select(&tr)
+        .filter(|row| row.select(&table).next().is_none())
+        .filter_map(|row| {
+            let mut cells = row.select(&td);
+            let first = cells.next()?;
+            Some((first, cells.last().unwrap_or(first)))
+        })
+        .collect()
+}
+
+fn parse_metadata(document: &Html, event: &mut ParsedTFREvent, warnings: &mut Vec<ParseWarning>) {
+    let font = selector("font");
+    for (label_cell, value_cell) in rows(document.root_element()) {
+        if label_cell.id() == value_cell.id() {
+            continue;
+        }
+        let label = normalize_label(&extract_text(&label_cell));
+        let Some((_, field)) = LABELS.iter().find(|(prefix, _)| label.starts_with(prefix)) else {
+            continue;
+        };
+        let mut value = match value_cell.select(&font).next() {
+            Some(font) if *field == "notam_id" => extract_text(&font),
+            _ => extract_text(&value_cell),
+        };
+        if *field == "notam_id" {
+            value = value.replace("FDC", "").trim().to_string();
+        }
+        let Some(current) = text_field(event, field) else {
+            continue;
+        };
+        if current.is_empty() {
+            *current = value;
+        } else if *current != value && !value.is_empty() {
+            warnings.push(ParseWarning {
+                field: field.to_string(),
+                issue: ParseIssue::Ambiguous,
+                detail: format!("'{current}' or '{value}'"),
+            });
+        }
+    }
+}
+
+/// A table can define several areas (stadium rings, VIP shelves): a
+/// repeated field starts the next one
+fn parse_airspace(document: &Html, event: &mut ParsedTFREvent) {
+    for table in innermost_tables(document, "Airspace Definition") {
+        let mut area = AirspaceArea::default();
+
+        for (label_cell, value_cell) in rows(table) {
+            let label = extract_text(&label_cell).to_lowercase();
+            // a label cell with no value cell next to it has no value
+            let value = if label_cell.id() == value_cell.id() && label.ends_with(':') {
+                String::new()
+            } else {
+                extract_text(&value_cell)
+            };
+            let label = label.trim_end_matches(':').trim();
+
+            if label.starts_with("center") {
+                if area.has_geometry() {
+                    event.airspace.areas.push(std::mem::take(&mut area));
+                }
+                area.center = value;
+            } else if label.starts_with("radius") {
+                if !area.radius.is_empty() {
+                    event.airspace.areas.push(std::mem::take(&mut area));
+                }
+                area.radius = value;
+            } else if label.starts_with("altitude") {
+                if !area.altitude.is_empty() {
+                    event.airspace.areas.push(std::mem::take(&mut area));
+                }
+                area.altitude = value;
+            } else if label.starts_with("effective date") {
+                event.airspace.effective.push(value);
+            } else if label.starts_with("latitude") || !geo::parse_coordinates(&value).is_empty() {
+                // polygon vertices, one or more per row
+                if !area.center.is_empty() || !area.altitude.is_empty() {
+                    event.airspace.areas.push(std::mem::take(&mut area));
+                }
+                if !area.boundary.is_empty() {
+                    area.boundary.push(' ');
+                }
+                area.boundary.push_str(&value);
+            }
+        }
+
+        if !area.is_empty() {
+            event.airspace.areas.push(area);
+        }
+    }
+}
+
+/// Whatever the page left out or we couldn't make sense of, after the
+/// derived fields are resolved
+fn check_fields(event: &mut ParsedTFREvent, warnings: &mut Vec<ParseWarning>) {
+    let missing = |field: &str| ParseWarning {
+        field: field.to_string(),
+        issue: ParseIssue::Missing,
+        detail: String::new(),
+    };
+    let unparsed = |field: &str, detail: &str| ParseWarning {
+        field: field.to_string(),
+        issue: ParseIssue::Unparsed,
+        detail: detail.to_string(),
+    };
+
+    for field in [
+        "notam_id",
+        "issue_date",
+        "location",
+        "begin",
+        "end",
+        "reason",
+        "type",
+    ] {
+        if text_field(event, field).is_some_and(|value| value.is_empty()) {
+            warnings.push(missing(field));
+        }
+    }
+    if !event.notam_id.is_empty()
+        && !event
+            .notam_id
+            .split_once('/')
+            .is_some_and(|(year, number)| {
+                [year, number]
+                    .iter()
+                    .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()))
+            })
+    {
+        warnings.push(unparsed("notam_id", &event.notam_id));
+    }
+    if !event.begin.is_empty() && schedule::parse_timestamp(&event.begin).is_err() {
+        warnings.push(unparsed("begin", &event.begin));
+    }
+    if !event.end.is_empty() && schedule::parse_end(&event.end).is_err() {
+        warnings.push(unparsed("end", &event.end));
+    }
+
+    if event.airspace.areas.is_empty() {
+        warnings.push(missing("airspace"));
+    } else if let Some(e) = event.airspace.geometry_error() {
+        warnings.push(unparsed("airspace", e));
+    }
+    if event.airspace.areas.iter().all(|a| a.altitude.is_empty()) {
+        warnings.push(missing("altitude"));
+    } else if let Some(e) = event.airspace.altitude_error() {
+        warnings.push(unparsed("altitude", e));
+    }
+    match &event.schedule_error {
+        Some(e) => warnings.push(unparsed("schedule", e)),
+        None if event.active_windows.is_empty() => warnings.push(missing("schedule")),
+        None => {}
+    }
+}
+
+/// The NOTAM as published: a `<pre>` holding it, or else the table under a
+/// "NOTAM Text" heading
+fn find_notam_text(document: &Html) -> String {
+    let published = document
+        .select(&selector("pre"))
+        .map(|pre| pre.text().collect::<String>())
+        .find(|text| text.contains("!FDC") || text.contains("Q)"));
+    if let Some(text) = published {
+        return text.trim().to_string();
+    }
+    innermost_tables(document, "NOTAM Text")
+        .first()
+        .map(|table| {
+            extract_text(table)
+                .replacen("NOTAM Text", "", 1)
+                .trim()
+                .to_string()
+        })
+        .unwrap_or_default()
+}
+
+/// "October 05, 2025 at 1400 UTC", as the tables write times
+fn page_time(time: DateTime<Utc>) -> String {
+    time.format("%B %d, %Y at %H%M UTC").to_string()
+}
+
+/// Whatever the tables left out that the NOTAM text has, before the
+/// derived fields are resolved
+fn fill_from_text(event: &mut ParsedTFREvent, text: &NotamText, warnings: &mut Vec<ParseWarning>) {
+    let mut recovered = |field: &str, value: &str| {
+        warnings.push(ParseWarning {
+            field: field.to_string(),
+            issue: ParseIssue::Recovered,
+            detail: value.to_string(),
+        })
+    };
+
+    if event.notam_id.is_empty()
+        && let Some(id) = &text.notam_id
+    {
+        event.notam_id = id.clone();
+        recovered("notam_id", id);
+    }
+    if let Some(start) = text.start {
+        if event.begin.is_empty() {
+            event.begin = page_time(start);
+            recovered("begin", &event.begin);
+        }
+        if event.end.is_empty() {
+            event.end = text.end.map_or("PERM".to_string(), page_time);
+            recovered("end", &event.end);
+        }
+    }
+
+    let altitude = text.altitude();
+    if event.airspace.areas.is_empty() {
+        if let (Some(center), Some(radius)) = (text.center(), text.radius()) {
+            recovered("airspace", &format!("{radius} around {center}"));
+            if let Some(altitude) = &altitude {
+                recovered("altitude", altitude);
+            }
+            event.airspace.areas.push(AirspaceArea {
+                center,
+                radius,
+                altitude: altitude.unwrap_or_default(),
+                ..Default::default()
+            });
+        }
+    } else if event.airspace.areas.iter().all(|a| a.altitude.is_empty())
+        && let Some(altitude) = altitude
+    {
+        recovered("altitude", &altitude);
+        for area in event.airspace.areas.iter_mut() {
+            area.altitude = altitude.clone();
+        }
+    }
+}
+
+/// Where the tables and the NOTAM text both say something, and it isn't
+/// the same thing. Only the text's own circle and limits are compared, not
+/// the `Q)` item's rounded ones.
+fn compare_with_text(event: &ParsedTFREvent, text: &NotamText, warnings: &mut Vec<ParseWarning>) {
+    let mut disagree = |field: &str, page: &dyn fmt::Display, published: &dyn fmt::Display| {
+        if !warnings.iter().any(|w| w.field == field) {
+            warnings.push(ParseWarning {
+                field: field.to_string(),
+                issue: ParseIssue::Ambiguous,
+                detail: format!("page says '{page}', NOTAM text says '{published}'"),
+            });
+        }
+    };
+
+    if let Some(id) = &text.notam_id
+        && !event.notam_id.is_empty()
+        && *id != event.notam_id
+    {
+        disagree("notam_id", &event.notam_id, id);
+    }
+    if let Some(start) = text.start {
+        if let Ok(begin) = schedule::parse_timestamp(&event.begin)
+            && begin != start
+        {
+            disagree("begin", &event.begin, &page_time(start));
+        }
+        if let Ok(end) = schedule::parse_end(&event.end)
+            && end != text.end
+        {
+            let published = text.end.map_or("PERM".to_string(), page_time);
+            disagree("end", &event.end, &published);
+        }
+    }
+
+    let circles: Vec<_> = event
+        .airspace
+        .areas
+        .iter()
+        .filter_map(|a| match &a.shape {
+            Some(Shape::Circle(circle)) => Some(circle),
+            _ => None,
+        })
+        .collect();
+    if let (Some(center), Some(radius)) = (&text.center, &text.radius)
+        && let Ok(published) = Circle::parse(center, radius)
+        && !circles.is_empty()
+        && !circles.iter().any(|c| {
+            geo::distance_nm(c.center, published.center) < 0.1
+                && (c.radius_nm - published.radius_nm).abs() < 0.05
+        })
+    {
+        let area = &event.airspace.areas[0];
+        let page = format!("{} around {}", area.radius, area.center);
+        disagree("airspace", &page, &format!("{radius} around {center}"));
+    }
+
+    let bands: Vec<_> = event
+        .airspace
+        .areas
+        .iter()
+        .filter_map(|a| a.altitude_band)
+        .collect();
+    if let Some(altitude) = &text.altitude
+        && let Ok(published) = altitude::parse_altitude(altitude)
+        && !bands.is_empty()
+        && !bands.contains(&published)
+    {
+        disagree("altitude", &bands[0], altitude);
+    }
+}
+
+/// Share of `EXPECTED_FIELDS` without a warning, 0 to 100. Fields
+/// recovered from the NOTAM text count as read.
+fn quality(warnings: &[ParseWarning]) -> u8 {
+    let clean = EXPECTED_FIELDS
+        .iter()
+        .filter(|field| {
+            !warnings
+                .iter()
+                .any(|w| w.field == **field && w.issue != ParseIssue::Recovered)
+        })
+        .count();
+    (clean * 100 / EXPECTED_FIELDS.len()) as u8
+}
+
+/// Parses a detail page into a `ParsedTFREvent`, with `parse_warnings` and
+/// `parse_quality` saying how well that went. Any HTML, however broken,
+/// gives an event.
+pub fn parse_notam_html(html_text: &str) -> ParsedTFREvent {
+    let document = Html::parse_document(html_text);
+    let mut event = ParsedTFREvent::default();
+    let mut warnings = Vec::new();
+
+    parse_metadata(&document, &mut event, &mut warnings);
+    parse_airspace(&document, &mut event);
+    if let Some(table) =
+        innermost_tables(&document, "Operating Restrictions and Requirements").first()
+    {
+        event.restrictions = extract_text(table);
+    }
+    if let Some(table) = innermost_tables(&document, "Other Information").first() {
+        event.other_info = extract_text(table);
+    }
+    event.notam_text = find_notam_text(&document);
+    let text = NotamText::parse(&event.notam_text);
+    fill_from_text(&mut event, &text, &mut warnings);
+
+    event.airspace.resolve();
+    event.resolve_schedule();
+    check_fields(&mut event, &mut warnings);
+    compare_with_text(&event, &text, &mut warnings);
+    event.parse_quality = Some(quality(&warnings));
+    event.parse_warnings = warnings;
+    event
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use std::fs;
+    use std::path::Path;
+
+    /// Detail pages under `tests/fixtures/detail`, each with the expected
+    /// `ParsedTFREvent` next to it as JSON. After a deliberate parser
+    /// change, rewrite the expectations with
+    /// `TFRALERT_BLESS=1 cargo test golden` and review the diff.
+    const GOLDEN_DIR: &str = "tests/fixtures/detail";
+
+    /// Where `actual` differs from `expected`, one line per leaf
+    fn json_diff(path: &str, expected: &Value, actual: &Value) -> Vec<String> {
+        match (expected, actual) {
+            (Value::Object(e), Value::Object(a)) => {
+                let keys: std::collections::BTreeSet<_> = e.keys().chain(a.keys()).collect();
+                keys.into_iter()
+                    .flat_map(|k| {
+                        let (e, a) = (e.get(k), a.get(k));
+                        let path = format!("{path}.{k}");
+                        json_diff(&path, e.unwrap_or(&Value::Null), a.unwrap_or(&Value::Null))
+                    })
+                    .collect()
+            }
+            (Value::Array(e), Value::Array(a)) => {
+                let mut diffs: Vec<_> = e
+                    .iter()
+                    .zip(a)
+                    .enumerate()
+                    .flat_map(|(i, (e, a))| json_diff(&format!("{path}[{i}]"), e, a))
+                    .collect();
+                if e.len() != a.len() {
+                    diffs.push(format!(
+                        "{path}: expected {} items, got {}",
+                        e.len(),
+                        a.len()
+                    ));
+                }
+                diffs
+            }
+            // serde_json reads floats back a last digit off
+            (Value::Number(e), Value::Number(a))
+                if (e.as_f64().unwrap() - a.as_f64().unwrap()).abs() < 1e-9 =>
+            {
+                Vec::new()
+            }
+            (e, a) if e == a => Vec::new(),
+            (e, a) => vec![format!("{path}: expected {e}, got {a}")],
+        }
+    }
+
+    #[test]
+    fn golden_detail_pages() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join(GOLDEN_DIR);
+        let bless = std::env::var_os("TFRALERT_BLESS").is_some();
+        let mut pages: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "html"))
+            .collect();
+        pages.sort();
+        assert!(!pages.is_empty(), "no fixtures in {}", dir.display());
+
+        let mut failures = Vec::new();
+        for page in &pages {
+            let parsed = parse_notam_html(&fs::read_to_string(page).unwrap());
+            let actual = serde_json::to_value(&parsed).unwrap();
+            let expected_path = page.with_extension("json");
+            let name = page.file_name().unwrap().to_string_lossy();
+
+            if bless {
+                let json = serde_json::to_string_pretty(&actual).unwrap() + "\n";
+                fs::write(&expected_path, json).unwrap();
+                continue;
+            }
+            let Ok(expected) = fs::read_to_string(&expected_path) else {
+                failures.push(format!("{name}: no {}", expected_path.display()));
+                continue;
+            };
+            let expected: Value = serde_json::from_str(&expected).unwrap();
+            let diffs = json_diff("", &expected, &actual);
+            if !diffs.is_empty() {
+                failures.push(format!("{name}:\n  {}", diffs.join("\n  ")));
+            }
+        }
+        assert!(
+            failures.is_empty(),
+            "parse_notam_html output changed:\n{}\n\
+             If that's intended, rerun with TFRALERT_BLESS=1 and review the new expectations",
+            failures.join("\n")
+        );
+    }
+
+    #[test]
+    fn json_diff_reports_paths() {
+        let expected = serde_json::json!({"a": {"b": [1, 2]}, "c": "x"});
+        let actual = serde_json::json!({"a": {"b": [1, 3, 4]}, "c": "x", "d": true});
+        assert_eq!(
+            json_diff("", &expected, &actual),
+            [
+                ".a.b[1]: expected 2, got 3",
+                ".a.b: expected 2 items, got 3",
+                ".d: expected null, got true",
+            ]
+        );
+    }
+
+    fn page(rows: &str) -> String {
+        format!("<html><body><table>{rows}</table></body></html>")
+    }
+
+    #[test]
+    fn labels_not_words_in_other_rows() {
+        let event = parse_notam_html(&page(
+            "<tr><td>Reason for NOTAM :</td><td>Type rating checkride area</td></tr>\
+             <tr><td>Type :</td><td>Hazards</td></tr>",
+        ));
+        assert_eq!(event.reason, "Type rating checkride area");
+        assert_eq!(event.r#type, "Hazards");
+    }
+
+    #[test]
+    fn conflicting_rows_are_ambiguous() {
+        let event = parse_notam_html(&page(
+            "<tr><td>Type :</td><td>Security</td></tr>\
+             <tr><td>Type :</td><td>VIP</td></tr>",
+        ));
+        assert_eq!(event.r#type, "Security");
+        let warning = event
+            .parse_warnings
+            .iter()
+            .find(|w| w.field == "type")
+            .unwrap();
+        assert_eq!(warning.issue, ParseIssue::Ambiguous);
+    }
+
+    #[test]
+    fn broken_html_still_parses() {
+        let html = fs::read_to_string(
+            Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join(GOLDEN_DIR)
+                .join("security_circle.html"),
+        )
+        .unwrap();
+        // cut off anywhere, as a dropped connection would
+        for (end, _) in html.char_indices().step_by(7) {
+            let event = parse_notam_html(&html[..end]);
+            assert!(event.parse_quality.is_some());
+        }
+        for html in [
+            "",
+            "<td></td>",
+            "<table><tr></tr><tr><td>Type :</td></tr></table>",
+        ] {
+            assert_eq!(parse_notam_html(html).parse_quality, Some(0));
+        }
+    }
+}
@@ -0,0 +1,128 @@
+// Copyright 2025. This file is part of TFRAlert.
+
+// TFRAlert is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+// TFRAlert is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License along with TFRAlert. If not, see <https://www.gnu.org/licenses/>.
+
+//! Content fingerprints, so an edited TFR is noticed even though its
+//! `notam_id` stays the same. FNV-1a: stable across runs and Rust versions,
+//! unlike `DefaultHasher`.
+
+use crate::logic::{ParsedTFREvent, RawTFREvent};
+
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+fn fnv1a<'a>(fields: impl IntoIterator<Item = &'a str>) -> u64 {
+    let mut hash = FNV_OFFSET;
+    for field in fields {
+        // the separator keeps ("ab", "c") and ("a", "bc") apart
+        for byte in field.trim().bytes().chain([0]) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(FNV_PRIME);
+        }
+    }
+    hash
+}
+
+/// The feed entry as downloaded, without our parsed details. A new
+/// modification time is enough to have the details fetched again.
+pub fn feed_entry(event: &RawTFREvent) -> u64 {
+    fnv1a([
+        event.notam_id.as_str(),
+        event.description.as_str(),
+        event.location.as_deref().unwrap_or(""),
+        event.r#type.as_deref().unwrap_or(""),
+        event.facility.as_deref().unwrap_or(""),
+        event.state.as_deref().unwrap_or(""),
+        event.mod_date.as_deref().unwrap_or(""),
+        event.mod_abs_time.as_deref().unwrap_or(""),
+    ])
+}
+
+/// Whether the feed entry changed between downloads. Fields `old` doesn't
+/// have at all, from a cache written before we kept them, don't count.
+pub fn feed_entry_changed(old: &RawTFREvent, new: &RawTFREvent) -> bool {
+    let known = |o: &Option<String>, n: &Option<String>| o.as_ref().and(n.clone());
+    let comparable = RawTFREvent {
+        notam_id: new.notam_id.clone(),
+        description: new.description.clone(),
+        location: new.location.clone(),
+        r#type: new.r#type.clone(),
+        facility: known(&old.facility, &new.facility),
+        state: known(&old.state, &new.state),
+        mod_date: known(&old.mod_date, &new.mod_date),
+        mod_abs_time: known(&old.mod_abs_time, &new.mod_abs_time),
+        ..Default::default()
+    };
+    feed_entry(old) != feed_entry(&comparable)
+}
+
+/// What the detail page says, leaving out everything we worked out from it
+pub fn details(event: &ParsedTFREvent) -> u64 {
+    let effective = event.airspace.effective.join("\n");
+    let airspace = event.airspace.text();
+    fnv1a([
+        event.notam_id.as_str(),
+        event.issue_date.as_str(),
+        event.location.as_str(),
+        event.begin.as_str(),
+        event.end.as_str(),
+        event.reason.as_str(),
+        event.r#type.as_str(),
+        event.replaced.as_str(),
+        airspace.as_str(),
+        effective.as_str(),
+        event.restrictions.as_str(),
+        event.other_info.as_str(),
+        event.description.as_str(),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_value() {
+        // FNV-1a of "a\0"
+        assert_eq!(fnv1a(["a"]), 0x089be207b544f1e4);
+        assert_ne!(fnv1a(["ab", "c"]), fnv1a(["a", "bc"]));
+    }
+
+    #[test]
+    fn details_ignore_derived_fields() {
+        let event = ParsedTFREvent {
+            notam_id: "5/1".to_string(),
+            end: "October 03, 2025 at 2300 UTC".to_string(),
+            ..Default::default()
+        };
+        let mut matched = event.clone();
+        matched.match_reasons = vec!["keyword 'stadium'".to_string()];
+        assert_eq!(details(&event), details(&matched));
+
+        let mut extended = event.clone();
+        extended.end = "October 04, 2025 at 0100 UTC".to_string();
+        assert_ne!(details(&event), details(&extended));
+    }
+
+    #[test]
+    fn newly_kept_fields_are_not_a_change() {
+        let old = RawTFREvent {
+            notam_id: "5/1".to_string(),
+            description: "ANNAPOLIS, MD".to_string(),
+            ..Default::default()
+        };
+        let mut new = RawTFREvent {
+            mod_abs_time: Some("202510011432".to_string()),
+            ..old.clone()
+        };
+        assert!(!feed_entry_changed(&old, &new));
+
+        let old = new.clone();
+        new.mod_abs_time = Some("202510021000".to_string());
+        assert!(feed_entry_changed(&old, &new));
+    }
+}
@@ -0,0 +1,499 @@
+// Copyright 2025. This file is part of TFRAlert.
+
+// TFRAlert is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+// TFRAlert is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License along with TFRAlert. If not, see <https://www.gnu.org/licenses/>.
+
+use crate::logic::Airspace;
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+
+/// Mean earth radius in nautical miles
+const EARTH_RADIUS_NM: f64 = 3440.065;
+const STATUTE_MILE_NM: f64 = 0.868_976;
+const KILOMETER_NM: f64 = 0.539_957;
+const FOOT_NM: f64 = 1.0 / 6076.115;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GeoError {
+    Coordinate(String),
+    Radius(String),
+    Polygon(String),
+}
+
+impl std::fmt::Display for GeoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GeoError::Coordinate(raw) => write!(f, "unrecognized coordinate '{raw}'"),
+            GeoError::Radius(raw) => write!(f, "unrecognized radius '{raw}'"),
+            GeoError::Polygon(raw) => write!(f, "boundary needs at least 3 points: '{raw}'"),
+        }
+    }
+}
+
+impl std::error::Error for GeoError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LatLon {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+/// A place we fly from, and how far out we care about TFRs.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WatchLocation {
+    pub name: String,
+    pub lat: f64,
+    pub lon: f64,
+    pub radius_nm: f64,
+}
+
+/// A circular airspace, numerically
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Circle {
+    pub center: LatLon,
+    pub radius_nm: f64,
+}
+
+impl Circle {
+    /// Circle from the raw detail-page strings
+    pub fn parse(center: &str, radius: &str) -> Result<Circle, GeoError> {
+        Ok(Circle {
+            center: parse_coordinate(center)?,
+            radius_nm: parse_radius_nm(radius)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Shape {
+    Circle(Circle),
+    Polygon { vertices: Vec<LatLon> },
+}
+
+impl Shape {
+    /// A polygon if there is boundary text, otherwise a circle
+    pub fn parse(center: &str, radius: &str, boundary: &str) -> Result<Shape, GeoError> {
+        if boundary.is_empty() {
+            return Circle::parse(center, radius).map(Shape::Circle);
+        }
+        let vertices = parse_coordinates(boundary);
+        if vertices.len() < 3 {
+            return Err(GeoError::Polygon(boundary.to_string()));
+        }
+        Ok(Shape::Polygon { vertices })
+    }
+
+    /// Does this shape come within `range_nm` of the point?
+    pub fn within_range(&self, point: LatLon, range_nm: f64) -> bool {
+        match self {
+            Shape::Circle(circle) => {
+                distance_nm(circle.center, point) <= circle.radius_nm + range_nm
+            }
+            Shape::Polygon { vertices } => {
+                // flat projection around the point, fine at TFR scales
+                let project = |v: &LatLon| {
+                    (
+                        (v.lon - point.lon) * 60.0 * point.lat.to_radians().cos(),
+                        (v.lat - point.lat) * 60.0,
+                    )
+                };
+                let pts: Vec<(f64, f64)> = vertices.iter().map(project).collect();
+                let edges = pts.iter().zip(pts.iter().cycle().skip(1));
+
+                let mut inside = false;
+                let mut nearest = f64::MAX;
+                for (&(x1, y1), &(x2, y2)) in edges {
+                    if (y1 > 0.0) != (y2 > 0.0) && x1 + (0.0 - y1) * (x2 - x1) / (y2 - y1) > 0.0 {
+                        inside = !inside;
+                    }
+                    let (dx, dy) = (x2 - x1, y2 - y1);
+                    let len2 = dx * dx + dy * dy;
+                    let t = if len2 > 0.0 {
+                        (-(x1 * dx + y1 * dy) / len2).clamp(0.0, 1.0)
+                    } else {
+                        0.0
+                    };
+                    nearest = nearest.min((x1 + t * dx).hypot(y1 + t * dy));
+                }
+                inside || nearest <= range_nm
+            }
+        }
+    }
+}
+
+impl WatchLocation {
+    pub fn position(&self) -> LatLon {
+        LatLon {
+            lat: self.lat,
+            lon: self.lon,
+        }
+    }
+}
+
+/// Great-circle (haversine) distance between two points.
+pub fn distance_nm(a: LatLon, b: LatLon) -> f64 {
+    let (lat1, lat2) = (a.lat.to_radians(), b.lat.to_radians());
+    let dlat = (b.lat - a.lat).to_radians();
+    let dlon = (b.lon - a.lon).to_radians();
+
+    let h = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_NM * h.sqrt().asin()
+}
+
+/// One half of a coordinate pair, e.g. "383000N" or "77º02'11"W".
+fn component_from_parts(parts: &[&str], hemisphere: char) -> Option<f64> {
+    let is_lat = matches!(hemisphere, 'N' | 'S');
+    let max = if is_lat { 90.0 } else { 180.0 };
+
+    let numbers: Vec<f64> = parts
+        .iter()
+        .map(|p| p.parse::<f64>())
+        .collect::<Result<_, _>>()
+        .ok()?;
+
+    let degrees = match numbers.as_slice() {
+        // packed FAA format: DDMM, DDMMSS, DDDMM, DDDMMSS (optionally with decimal seconds)
+        [single] if parts[0].split('.').next()?.len() > 3 => {
+            let digits = parts[0].split('.').next()?;
+            let deg_len = if is_lat { 2 } else { 3 };
+            if digits.len() < deg_len + 2 {
+                return None;
+            }
+            let deg: f64 = digits[..deg_len].parse().ok()?;
+            let min: f64 = digits[deg_len..deg_len + 2].parse().ok()?;
+            let whole_sec = digits.len() > deg_len + 2;
+            let sec = if whole_sec {
+                single - (digits[..deg_len + 2].parse::<f64>().ok()? * 100.0)
+            } else {
+                0.0
+            };
+            if min >= 60.0 || sec >= 60.0 {
+                return None;
+            }
+            deg + min / 60.0 + sec / 3600.0
+        }
+        [deg] => *deg,
+        [deg, min] if *min < 60.0 => deg + min / 60.0,
+        [deg, min, sec] if *min < 60.0 && *sec < 60.0 => deg + min / 60.0 + sec / 3600.0,
+        _ => return None,
+    };
+
+    if degrees > max {
+        return None;
+    }
+
+    Some(match hemisphere {
+        'S' | 'W' => -degrees,
+        _ => degrees,
+    })
+}
+
+/// All coordinate pairs found in a piece of FAA text, in order.
+///
+/// Understands the packed form ("383000N0770000W") as well as the
+/// degree/minute/second form used on the detail pages
+/// ("Latitude: 38º53'06"N, Longitude: 77º02'11"W").
+pub fn parse_coordinates(text: &str) -> Vec<LatLon> {
+    let normalized: String = text
+        .chars()
+        .map(|c| match c {
+            'º' | '°' | '\'' | '"' | '′' | '″' | ',' | ':' | '(' | ')' | '-' => ' ',
+            _ => c,
+        })
+        .collect();
+
+    let mut coords = Vec::new();
+    let mut numbers: Vec<&str> = Vec::new();
+    let mut pending_lat: Option<f64> = None;
+
+    let mut rest = normalized.as_str();
+    while !rest.is_empty() {
+        let c = rest.chars().next().unwrap_or(' ');
+        if c.is_ascii_digit() || c == '.' {
+            let end = rest
+                .find(|ch: char| !(ch.is_ascii_digit() || ch == '.'))
+                .unwrap_or(rest.len());
+            numbers.push(&rest[..end]);
+            rest = &rest[end..];
+            continue;
+        }
+        if c.is_alphabetic() {
+            let end = rest
+                .find(|ch: char| !ch.is_alphabetic())
+                .unwrap_or(rest.len());
+            let word = &rest[..end];
+            rest = &rest[end..];
+
+            let hemisphere = word.chars().next().map(|h| h.to_ascii_uppercase());
+            match hemisphere {
+                Some(h @ ('N' | 'S' | 'E' | 'W')) if word.len() == 1 && !numbers.is_empty() => {
+                    let value = component_from_parts(&numbers, h);
+                    match (h, value, pending_lat) {
+                        ('N' | 'S', Some(lat), _) => pending_lat = Some(lat),
+                        ('E' | 'W', Some(lon), Some(lat)) => {
+                            coords.push(LatLon { lat, lon });
+                            pending_lat = None;
+                        }
+                        _ => pending_lat = None,
+                    }
+                }
+                _ => {}
+            }
+            numbers.clear();
+            continue;
+        }
+        rest = &rest[c.len_utf8()..];
+    }
+
+    coords
+}
+
+/// First coordinate pair in the text.
+pub fn parse_coordinate(text: &str) -> Result<LatLon, GeoError> {
+    parse_coordinates(text)
+        .into_iter()
+        .next()
+        .ok_or_else(|| GeoError::Coordinate(text.to_string()))
+}
+
+/// Radius text such as "3 nautical miles", "30NM" or "3000 feet", in nautical miles.
+pub fn parse_radius_nm(text: &str) -> Result<f64, GeoError> {
+    let err = || GeoError::Radius(text.to_string());
+    let lower = text.to_lowercase();
+
+    // number may be glued to its unit, e.g. "30NM"
+    let start = lower.find(|c: char| c.is_ascii_digit()).ok_or_else(err)?;
+    let end = lower[start..]
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .map_or(lower.len(), |i| start + i);
+    let value: f64 = lower[start..end].parse().map_err(|_| err())?;
+    // whole words only: "mi" and "miles" are not metres
+    let unit = lower[end..]
+        .trim_start()
+        .split(|c: char| !c.is_alphabetic())
+        .next()
+        .unwrap_or("");
+
+    let factor = match unit {
+        // TFR radii are published in nautical miles, and the larger unit is the safer guess
+        "" | "nm" | "nautical" | "mi" | "mile" | "miles" => 1.0,
+        "sm" | "statute" => STATUTE_MILE_NM,
+        "ft" | "feet" | "foot" => FOOT_NM,
+        "km" | "kilometer" | "kilometers" | "kilometre" | "kilometres" => KILOMETER_NM,
+        "m" | "meter" | "meters" | "metre" | "metres" => KILOMETER_NM / 1000.0,
+        _ => return Err(err()),
+    };
+
+    Ok(value * factor)
+}
+
+/// The first of our locations any area of the airspace comes within range
+/// of, as a human-readable reason; `None` if it's nowhere near.
+/// Areas we can't place on a map count as in range.
+pub fn near_watch_location(airspace: &Airspace, locations: &[WatchLocation]) -> Option<String> {
+    if airspace.areas.is_empty() {
+        warn!("No airspace areas to place, keeping it");
+        return Some("no airspace definition, kept".to_string());
+    }
+
+    airspace.areas.iter().find_map(|area| {
+        let Some(shape) = &area.shape else {
+            warn!(
+                "Could not place airspace area (center: '{}', radius: '{}', boundary: '{}'), keeping it",
+                area.center, area.radius, area.boundary
+            );
+            return Some("location not parsed, kept".to_string());
+        };
+
+        locations.iter().find_map(|loc| {
+            if !shape.within_range(loc.position(), loc.radius_nm) {
+                return None;
+            }
+            debug!("Airspace area is within range of {}", loc.name);
+            Some(match shape {
+                Shape::Circle(circle) => format!(
+                    "{:.1} NM from {}",
+                    (distance_nm(circle.center, loc.position()) - circle.radius_nm).max(0.0),
+                    loc.name
+                ),
+                Shape::Polygon { .. } => format!("within {} NM of {}", loc.radius_nm, loc.name),
+            })
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logic::AirspaceArea;
+
+    fn close(a: f64, b: f64, tolerance: f64) -> bool {
+        (a - b).abs() <= tolerance
+    }
+
+    #[test]
+    fn packed_coordinates() {
+        let c = parse_coordinate("383000N0770000W").unwrap();
+        assert_eq!((c.lat, c.lon), (38.5, -77.0));
+
+        let c = parse_coordinate("385837N0762910W").unwrap();
+        assert!(close(c.lat, 38.0 + 58.0 / 60.0 + 37.0 / 3600.0, 1e-9));
+        assert!(close(c.lon, -(76.0 + 29.0 / 60.0 + 10.0 / 3600.0), 1e-9));
+
+        // degrees and minutes only, and decimal seconds
+        let c = parse_coordinate("3858N07629W").unwrap();
+        assert!(close(c.lat, 38.0 + 58.0 / 60.0, 1e-9));
+        let c = parse_coordinate("385837.5S0762910.5E").unwrap();
+        assert!(close(c.lat, -(38.0 + 58.0 / 60.0 + 37.5 / 3600.0), 1e-9));
+        assert!(close(c.lon, 76.0 + 29.0 / 60.0 + 10.5 / 3600.0, 1e-9));
+    }
+
+    #[test]
+    fn degree_minute_second_coordinates() {
+        let c = parse_coordinate("Latitude: 38º53'06\"N, Longitude: 77º02'11\"W").unwrap();
+        assert!(close(c.lat, 38.0 + 53.0 / 60.0 + 6.0 / 3600.0, 1e-9));
+        assert!(close(c.lon, -(77.0 + 2.0 / 60.0 + 11.0 / 3600.0), 1e-9));
+
+        let c = parse_coordinate("38°53′N 77°02′W").unwrap();
+        assert!(close(c.lon, -(77.0 + 2.0 / 60.0), 1e-9));
+
+        let all = parse_coordinates("383000N0770000W to 390000N0770000W (390000N0763000W)");
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[2].lon, -76.5);
+    }
+
+    #[test]
+    fn bad_coordinates() {
+        // minutes past 60, latitude past 90, no longitude
+        for text in [
+            "386100N0770000W",
+            "953000N0770000W",
+            "383000N",
+            "",
+            "See remarks",
+        ] {
+            assert!(parse_coordinate(text).is_err(), "{text}");
+        }
+        assert_eq!(
+            parse_coordinate("nowhere").unwrap_err().to_string(),
+            "unrecognized coordinate 'nowhere'"
+        );
+    }
+
+    #[test]
+    fn radius_units() {
+        let nm = |text| parse_radius_nm(text).unwrap();
+        assert_eq!(nm("3 NM"), 3.0);
+        assert_eq!(nm("3 nautical miles"), 3.0);
+        assert_eq!(nm("30NM"), 30.0);
+        assert_eq!(nm("3"), 3.0);
+        assert_eq!(nm("3 mi"), 3.0);
+        assert_eq!(nm("3 miles"), 3.0);
+        assert_eq!(nm("1.5 Mile radius"), 1.5);
+        assert!(close(nm("10 SM"), 8.68976, 1e-9));
+        assert!(close(nm("10 statute miles"), 8.68976, 1e-9));
+        assert!(close(nm("6076.115 ft"), 1.0, 1e-9));
+        assert!(close(nm("3000 feet"), 0.4937, 1e-4));
+        assert!(close(nm("10 km"), 5.39957, 1e-9));
+        assert!(close(nm("1000 m"), 0.539957, 1e-9));
+        assert!(close(nm("1000 meters"), 0.539957, 1e-9));
+
+        for text in ["", "NM", "3 furlongs", "3 mix"] {
+            assert!(parse_radius_nm(text).is_err(), "{text}");
+        }
+    }
+
+    #[test]
+    fn haversine() {
+        let origin = LatLon { lat: 0.0, lon: 0.0 };
+        let north = LatLon { lat: 1.0, lon: 0.0 };
+        assert!(close(distance_nm(origin, north), 60.04, 0.01));
+        assert_eq!(distance_nm(north, north), 0.0);
+
+        let jfk = LatLon {
+            lat: 40.6398,
+            lon: -73.7789,
+        };
+        let lax = LatLon {
+            lat: 33.9425,
+            lon: -118.4081,
+        };
+        assert!(close(distance_nm(jfk, lax), 2145.9, 0.1));
+        assert_eq!(distance_nm(jfk, lax), distance_nm(lax, jfk));
+    }
+
+    #[test]
+    fn shapes() {
+        let circle = Shape::parse("383000N0770000W", "3 NM", "").unwrap();
+        assert!(matches!(circle, Shape::Circle(c) if c.radius_nm == 3.0));
+
+        // a square about 30 NM on a side
+        let square = Shape::parse(
+            "",
+            "",
+            "390000N0770000W 390000N0763000W 383000N0763000W 383000N0770000W",
+        )
+        .unwrap();
+        let Shape::Polygon { vertices } = &square else {
+            panic!("{square:?}");
+        };
+        assert_eq!(vertices.len(), 4);
+
+        let at = |lat, lon| LatLon { lat, lon };
+        assert!(square.within_range(at(38.75, -76.75), 0.0));
+        // 0.1° (6 NM) north of the top edge
+        assert!(!square.within_range(at(39.1, -76.75), 5.0));
+        assert!(square.within_range(at(39.1, -76.75), 7.0));
+        // past a corner, where the nearest point is the vertex
+        assert!(!square.within_range(at(39.1, -76.4), 7.0));
+        assert!(square.within_range(at(39.1, -76.4), 10.0));
+
+        assert!(matches!(
+            Shape::parse("", "", "390000N0770000W 390000N0763000W"),
+            Err(GeoError::Polygon(_))
+        ));
+    }
+
+    #[test]
+    fn only_areas_near_a_location_are_kept() {
+        let home = WatchLocation {
+            name: "Home".to_string(),
+            lat: 38.8977,
+            lon: -77.0365,
+            radius_nm: 2.0,
+        };
+        // 0.1° of longitude east is 4.67 NM here
+        let area = |radius_nm| {
+            let mut area = AirspaceArea {
+                center: "385352N0765611W".to_string(),
+                radius: format!("{radius_nm} NM"),
+                ..Default::default()
+            };
+            area.resolve_geometry();
+            Airspace {
+                areas: vec![area],
+                effective: Vec::new(),
+            }
+        };
+
+        assert_eq!(
+            near_watch_location(&area(1.0), std::slice::from_ref(&home)),
+            None
+        );
+        let reason = near_watch_location(&area(3.0), std::slice::from_ref(&home)).unwrap();
+        assert_eq!(reason, "1.7 NM from Home");
+
+        let unplaced = Airspace {
+            areas: vec![AirspaceArea {
+                center: "somewhere".to_string(),
+                ..Default::default()
+            }],
+            effective: Vec::new(),
+        };
+        assert!(near_watch_location(&unplaced, &[home]).is_some());
+    }
+}
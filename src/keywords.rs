@@ -0,0 +1,556 @@
+// Copyright 2025. This file is part of TFRAlert.
+
+// TFRAlert is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+// TFRAlert is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License along with TFRAlert. If not, see <https://www.gnu.org/licenses/>.
+
+//! Keyword rules, one per line:
+//!
+//! ```text
+//! stadium                          # plain keyword, anywhere in the TFR
+//! "white house"                    # phrase
+//! /fl\d{3}/                        # regular expression (case-insensitive)
+//! location:annapolis               # only in one field
+//! reason:vip AND location:"new york"
+//! (stadium OR sporting) -practice  # grouping; terms side by side are ANDed
+//! -exercise                        # a line of only negated terms excludes
+//! ```
+//!
+//! A TFR matches if any line matches and no exclusion line does.
+
+use crate::logic::ParsedTFREvent;
+use regex::{Regex, RegexBuilder};
+
+/// Parts of a parsed TFR a term can be scoped to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Notam,
+    Location,
+    Reason,
+    Type,
+    Description,
+    Restrictions,
+    Other,
+    Airspace,
+    Dates,
+}
+
+impl Field {
+    fn from_name(name: &str) -> Option<Field> {
+        Some(match name.to_lowercase().as_str() {
+            "notam" | "id" => Field::Notam,
+            "location" | "loc" => Field::Location,
+            "reason" => Field::Reason,
+            "type" => Field::Type,
+            "description" | "desc" => Field::Description,
+            "restrictions" => Field::Restrictions,
+            "other" => Field::Other,
+            "airspace" | "altitude" => Field::Airspace,
+            "dates" | "date" => Field::Dates,
+            _ => return None,
+        })
+    }
+
+    fn text(&self, event: &ParsedTFREvent) -> String {
+        match self {
+            Field::Notam => event.notam_id.clone(),
+            Field::Location => event.location.clone(),
+            Field::Reason => event.reason.clone(),
+            Field::Type => event.r#type.clone(),
+            Field::Description => event.description.clone(),
+            Field::Restrictions => event.restrictions.clone(),
+            Field::Other => event.other_info.clone(),
+            Field::Airspace => event.airspace.text(),
+            Field::Dates => format!(
+                "{} {} {} {}",
+                event.issue_date,
+                event.begin,
+                event.end,
+                event.airspace.effective.join(" ")
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    /// lowercase
+    Text(String),
+    Regex(Regex),
+}
+
+impl Pattern {
+    fn is_match(&self, haystack: &str) -> bool {
+        match self {
+            Pattern::Text(text) => haystack.to_lowercase().contains(text),
+            Pattern::Regex(re) => re.is_match(haystack),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum KeywordExpr {
+    /// No field means anywhere in the TFR
+    Term {
+        field: Option<Field>,
+        pattern: Pattern,
+    },
+    Not(Box<KeywordExpr>),
+    And(Vec<KeywordExpr>),
+    Or(Vec<KeywordExpr>),
+}
+
+impl KeywordExpr {
+    pub fn matches(&self, event: &ParsedTFREvent) -> bool {
+        match self {
+            KeywordExpr::Term { field, pattern } => match field {
+                Some(field) => pattern.is_match(&field.text(event)),
+                None => pattern.is_match(&searchable_text(event)),
+            },
+            KeywordExpr::Not(inner) => !inner.matches(event),
+            KeywordExpr::And(all) => all.iter().all(|e| e.matches(event)),
+            KeywordExpr::Or(any) => any.iter().any(|e| e.matches(event)),
+        }
+    }
+
+    /// For a line of only negated terms ("-exercise -test"), the terms it
+    /// excludes ("exercise OR test")
+    fn excluded_terms(&self) -> Option<KeywordExpr> {
+        match self {
+            KeywordExpr::Not(inner) => Some((**inner).clone()),
+            KeywordExpr::And(all) => all
+                .iter()
+                .map(KeywordExpr::excluded_terms)
+                .collect::<Option<Vec<_>>>()
+                .map(KeywordExpr::Or),
+            _ => None,
+        }
+    }
+}
+
+/// Everything a plain keyword is searched in
+pub fn searchable_text(event: &ParsedTFREvent) -> String {
+    format!(
+        "{} {} {} {} {} {} {} {} {} {}",
+        event.notam_id,
+        event.r#type,
+        event.location,
+        event.reason,
+        event.begin,
+        event.end,
+        event.restrictions,
+        event.other_info,
+        event.airspace.text(),
+        event.description
+    )
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeywordError {
+    pub line: String,
+    pub reason: String,
+}
+
+impl std::fmt::Display for KeywordError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "bad keyword rule '{}': {}", self.line, self.reason)
+    }
+}
+
+impl std::error::Error for KeywordError {}
+
+/// One keyword line and what it parsed to
+#[derive(Debug, Clone)]
+pub struct KeywordRule {
+    pub line: String,
+    pub expr: KeywordExpr,
+}
+
+impl KeywordRule {
+    pub fn matches(&self, event: &ParsedTFREvent) -> bool {
+        self.expr.matches(event)
+    }
+}
+
+/// All keyword lines, split into rules that select TFRs and rules that
+/// exclude them. A TFR matches if any include rule matches (or there are
+/// none) and no exclude rule does.
+#[derive(Debug, Clone, Default)]
+pub struct KeywordRules {
+    pub include: Vec<KeywordRule>,
+    /// Stored as the terms to exclude, "-exercise" matches "exercise"
+    pub exclude: Vec<KeywordRule>,
+}
+
+impl KeywordRules {
+    pub fn parse(lines: &[String]) -> Result<KeywordRules, KeywordError> {
+        let mut rules = KeywordRules::default();
+        for line in lines {
+            let Some(expr) = parse_line(line)? else {
+                continue;
+            };
+            let line = line.trim().to_string();
+            match expr.excluded_terms() {
+                Some(excluded) => rules.exclude.push(KeywordRule {
+                    line,
+                    expr: excluded,
+                }),
+                None => rules.include.push(KeywordRule { line, expr }),
+            }
+        }
+        Ok(rules)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.include.len() + self.exclude.len()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    And,
+    Or,
+    Not,
+    Term(Option<Field>, RawPattern),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum RawPattern {
+    Text(String),
+    Regex(String),
+}
+
+fn tokenize(line: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        match c {
+            '#' => break,
+            '(' => {
+                tokens.push(Token::LParen);
+                i += 1;
+                continue;
+            }
+            ')' => {
+                tokens.push(Token::RParen);
+                i += 1;
+                continue;
+            }
+            '|' => {
+                tokens.push(Token::Or);
+                i += 1;
+                continue;
+            }
+            '-' | '!' => {
+                tokens.push(Token::Not);
+                i += 1;
+                continue;
+            }
+            _ => {}
+        }
+
+        // optional "field:" scope
+        let mut field = None;
+        let word_end = (i..chars.len())
+            .find(|&j| !chars[j].is_alphabetic())
+            .unwrap_or(chars.len());
+        if word_end > i && chars.get(word_end) == Some(&':') {
+            let name: String = chars[i..word_end].iter().collect();
+            if let Some(f) = Field::from_name(&name) {
+                field = Some(f);
+                i = word_end + 1;
+            }
+        }
+
+        let (pattern, next) = match chars.get(i) {
+            Some('"') => {
+                let end = (i + 1..chars.len())
+                    .find(|&j| chars[j] == '"')
+                    .ok_or("unclosed quote")?;
+                let text: String = chars[i + 1..end].iter().collect();
+                (RawPattern::Text(text), end + 1)
+            }
+            Some('/') => {
+                let end = (i + 1..chars.len())
+                    .find(|&j| chars[j] == '/' && chars[j - 1] != '\\')
+                    .ok_or("unclosed regular expression")?;
+                let re: String = chars[i + 1..end].iter().collect();
+                (RawPattern::Regex(re), end + 1)
+            }
+            _ => {
+                let end = (i..chars.len())
+                    .find(|&j| chars[j].is_whitespace() || chars[j] == ')' || chars[j] == '(')
+                    .unwrap_or(chars.len());
+                let word: String = chars[i..end].iter().collect();
+                match (field, word.as_str()) {
+                    (None, "AND") => {
+                        tokens.push(Token::And);
+                        i = end;
+                        continue;
+                    }
+                    (None, "OR") => {
+                        tokens.push(Token::Or);
+                        i = end;
+                        continue;
+                    }
+                    (None, "NOT") => {
+                        tokens.push(Token::Not);
+                        i = end;
+                        continue;
+                    }
+                    (_, "") => return Err("missing value after field".to_string()),
+                    _ => (RawPattern::Text(word), end),
+                }
+            }
+        };
+        tokens.push(Token::Term(field, pattern));
+        i = next;
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    // expr := and ("OR" and)*
+    fn expr(&mut self) -> Result<KeywordExpr, String> {
+        let mut any = vec![self.and()?];
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            any.push(self.and()?);
+        }
+        Ok(if any.len() == 1 {
+            any.remove(0)
+        } else {
+            KeywordExpr::Or(any)
+        })
+    }
+
+    // and := unary ("AND"? unary)*
+    fn and(&mut self) -> Result<KeywordExpr, String> {
+        let mut all = vec![self.unary()?];
+        loop {
+            match self.peek() {
+                Some(Token::And) => {
+                    self.next();
+                }
+                Some(Token::Not | Token::LParen | Token::Term(..)) => {}
+                _ => break,
+            }
+            all.push(self.unary()?);
+        }
+        Ok(if all.len() == 1 {
+            all.remove(0)
+        } else {
+            KeywordExpr::And(all)
+        })
+    }
+
+    // unary := "NOT" unary | "(" expr ")" | term
+    fn unary(&mut self) -> Result<KeywordExpr, String> {
+        match self.next() {
+            Some(Token::Not) => Ok(KeywordExpr::Not(Box::new(self.unary()?))),
+            Some(Token::LParen) => {
+                let inner = self.expr()?;
+                match self.next() {
+                    Some(Token::RParen) => Ok(inner),
+                    _ => Err("missing ')'".to_string()),
+                }
+            }
+            Some(Token::Term(field, raw)) => {
+                let pattern = match raw {
+                    RawPattern::Text(text) => Pattern::Text(text.to_lowercase()),
+                    RawPattern::Regex(re) => Pattern::Regex(
+                        RegexBuilder::new(&re)
+                            .case_insensitive(true)
+                            .build()
+                            .map_err(|e| e.to_string())?,
+                    ),
+                };
+                Ok(KeywordExpr::Term { field, pattern })
+            }
+            Some(token) => Err(format!("unexpected {token:?}")),
+            None => Err("unexpected end of rule".to_string()),
+        }
+    }
+}
+
+/// One line of the keyword file. Blank lines and comments give `None`.
+pub fn parse_line(line: &str) -> Result<Option<KeywordExpr>, KeywordError> {
+    let err = |reason: String| KeywordError {
+        line: line.to_string(),
+        reason,
+    };
+
+    let tokens = tokenize(line).map_err(err)?;
+    if tokens.is_empty() {
+        return Ok(None);
+    }
+
+    let mut parser = Parser { tokens, pos: 0 };
+    let expr = parser.expr().map_err(err)?;
+    if let Some(token) = parser.peek() {
+        return Err(err(format!("unexpected {token:?}")));
+    }
+    Ok(Some(expr))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event() -> ParsedTFREvent {
+        ParsedTFREvent {
+            notam_id: "5/1234".to_string(),
+            location: "Annapolis, MD".to_string(),
+            reason: "VIP movement".to_string(),
+            r#type: "SECURITY".to_string(),
+            description: "Navy football practice at the stadium".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn matches(line: &str) -> bool {
+        parse_line(line).unwrap().unwrap().matches(&event())
+    }
+
+    fn error(line: &str) -> String {
+        parse_line(line).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn tokens() {
+        let text = |t: &str| Token::Term(None, RawPattern::Text(t.to_string()));
+        assert_eq!(
+            tokenize(r#"(a OR "b c") AND -loc:/d\/e/ # note"#).unwrap(),
+            [
+                Token::LParen,
+                text("a"),
+                Token::Or,
+                text("b c"),
+                Token::RParen,
+                Token::And,
+                Token::Not,
+                Token::Term(
+                    Some(Field::Location),
+                    RawPattern::Regex(r"d\/e".to_string())
+                ),
+            ]
+        );
+        // lowercase operators and unknown fields are plain words
+        assert_eq!(
+            tokenize("or nowhere:x").unwrap(),
+            [text("or"), text("nowhere:x")]
+        );
+        assert!(tokenize("  # only a comment").unwrap().is_empty());
+        assert!(parse_line("").unwrap().is_none());
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        // (stadium AND rodeo) OR vip
+        assert!(matches("stadium rodeo OR vip"));
+        assert!(matches("stadium rodeo | vip"));
+        // stadium AND (rodeo OR vip)
+        assert!(matches("stadium (rodeo OR vip)"));
+        assert!(!matches("rodeo (stadium OR vip)"));
+        assert!(!matches("stadium AND rodeo"));
+        assert!(matches("STADIUM"));
+    }
+
+    #[test]
+    fn negation() {
+        assert!(!matches("stadium -practice"));
+        assert!(matches("stadium NOT rodeo"));
+        assert!(matches("!(rodeo OR concert)"));
+        assert!(!matches("NOT NOT rodeo"));
+    }
+
+    #[test]
+    fn phrases_and_regex() {
+        assert!(matches("\"football practice\""));
+        assert!(!matches("\"practice football\""));
+        assert!(matches("/foot\\w+ pract/"));
+        assert!(matches("/^5\\/\\d{4}/"));
+        assert!(!matches("/^6\\//"));
+    }
+
+    #[test]
+    fn field_scope() {
+        assert!(matches("location:annapolis"));
+        assert!(!matches("reason:annapolis"));
+        assert!(matches("reason:vip AND loc:\"annapolis, md\""));
+        assert!(matches("type:security"));
+        assert!(!matches("type:vip"));
+        assert!(!matches("type:security AND type:hazards"));
+        // the type is searched without a scope too
+        assert!(matches("security"));
+        assert!(matches("desc:/stadium$/"));
+        assert!(matches("id:5/1234"));
+    }
+
+    #[test]
+    fn error_messages() {
+        assert_eq!(error("\"open"), "bad keyword rule '\"open': unclosed quote");
+        assert_eq!(
+            error("/open"),
+            "bad keyword rule '/open': unclosed regular expression"
+        );
+        assert_eq!(error("(a OR b"), "bad keyword rule '(a OR b': missing ')'");
+        assert_eq!(
+            error("a OR"),
+            "bad keyword rule 'a OR': unexpected end of rule"
+        );
+        assert_eq!(error("a )"), "bad keyword rule 'a )': unexpected RParen");
+        assert_eq!(
+            error("location: x"),
+            "bad keyword rule 'location: x': missing value after field"
+        );
+        assert!(error("/(/").starts_with("bad keyword rule '/(/': regex parse error"));
+    }
+
+    #[test]
+    fn include_and_exclude_lines() {
+        let lines = ["stadium", "-practice -exercise", "# comment", "vip -rodeo"]
+            .map(String::from)
+            .to_vec();
+        let rules = KeywordRules::parse(&lines).unwrap();
+        assert_eq!(rules.len(), 3);
+        let include: Vec<_> = rules.include.iter().map(|r| r.line.as_str()).collect();
+        assert_eq!(include, ["stadium", "vip -rodeo"]);
+        // stored as what it excludes
+        assert_eq!(rules.exclude[0].line, "-practice -exercise");
+        assert!(rules.exclude[0].matches(&event()));
+
+        assert!(KeywordRules::parse(&["(".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,507 @@
+// Copyright 2025. This file is part of TFRAlert.
+
+// TFRAlert is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+// TFRAlert is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License along with TFRAlert. If not, see <https://www.gnu.org/licenses/>.
+
+//! What happened to each TFR between two feed downloads: published,
+//! in force, edited, replaced by a newer NOTAM, pulled early, or run out.
+
+use crate::logic::{ParsedTFREvent, RawTFREvent};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LifecycleState {
+    /// First seen in the feed
+    New,
+    /// Its first active window has started
+    Active,
+    /// The feed entry changed
+    Modified,
+    /// Replaced by a newer NOTAM for the same TFR
+    Superseded,
+    /// Gone from the feed before it ended
+    Cancelled,
+    /// Its last active window is over
+    Expired,
+}
+
+impl LifecycleState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LifecycleState::New => "new",
+            LifecycleState::Active => "active",
+            LifecycleState::Modified => "modified",
+            LifecycleState::Superseded => "superseded",
+            LifecycleState::Cancelled => "cancelled",
+            LifecycleState::Expired => "expired",
+        }
+    }
+
+    pub fn parse(text: &str) -> Option<LifecycleState> {
+        serde_json::from_value(serde_json::Value::String(text.to_string())).ok()
+    }
+
+    /// Nothing more will happen to it
+    pub fn is_final(&self) -> bool {
+        matches!(
+            self,
+            LifecycleState::Superseded | LifecycleState::Cancelled | LifecycleState::Expired
+        )
+    }
+}
+
+impl std::fmt::Display for LifecycleState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// One entry in a TFR's history
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LifecycleChange {
+    pub notam_id: String,
+    pub state: LifecycleState,
+    pub at: DateTime<Utc>,
+    #[serde(default)]
+    pub location: String,
+    /// e.g. "end, airspace changed"
+    #[serde(default)]
+    pub detail: String,
+    /// Field by field, when the details changed
+    #[serde(default)]
+    pub changes: Vec<FieldChange>,
+}
+
+impl LifecycleChange {
+    /// "TFR 5/1234 cancelled (ANNAPOLIS, MD)"
+    pub fn describe(&self) -> String {
+        let mut text = format!("TFR {} {}", self.notam_id, self.state);
+        if !self.location.is_empty() {
+            text.push_str(&format!(" ({})", self.location));
+        }
+        if !self.detail.is_empty() {
+            text.push_str(&format!(": {}", self.detail));
+        }
+        text
+    }
+
+    /// `old` was replaced by `new_id`
+    pub fn superseded(old: &ParsedTFREvent, new_id: &str, now: DateTime<Utc>) -> LifecycleChange {
+        LifecycleChange {
+            notam_id: old.notam_id.clone(),
+            state: LifecycleState::Superseded,
+            at: now,
+            location: old.location.clone(),
+            detail: format!("replaced by {new_id}"),
+            changes: Vec::new(),
+        }
+    }
+}
+
+/// One field that differs between two versions of a TFR
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub old: String,
+    pub new: String,
+}
+
+impl std::fmt::Display for FieldChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let text = |s: &str| {
+            if s.is_empty() {
+                "(none)".to_string()
+            } else {
+                s.to_string()
+            }
+        };
+        write!(
+            f,
+            "{}: {} → {}",
+            self.field,
+            text(&self.old),
+            text(&self.new)
+        )
+    }
+}
+
+/// Fields a pilot cares about that differ between `old` and `new`
+pub fn diff_events(old: &ParsedTFREvent, new: &ParsedTFREvent) -> Vec<FieldChange> {
+    let fields = |e: &ParsedTFREvent| {
+        [
+            ("location", e.location.clone()),
+            ("type", e.r#type.clone()),
+            ("reason", e.reason.clone()),
+            ("begin", e.begin.clone()),
+            ("end", e.end.clone()),
+            ("effective", e.airspace.effective.join("; ")),
+            ("airspace", e.airspace.text()),
+            ("restrictions", e.restrictions.clone()),
+        ]
+    };
+    fields(old)
+        .into_iter()
+        .zip(fields(new))
+        .filter(|((_, a), (_, b))| a.trim() != b.trim())
+        .map(|((field, old), (_, new))| FieldChange {
+            field: field.to_string(),
+            old: old.trim().to_string(),
+            new: new.trim().to_string(),
+        })
+        .collect()
+}
+
+/// Every active window is over. Unknown schedules never end.
+fn has_ended(parsed: Option<&ParsedTFREvent>, now: DateTime<Utc>) -> bool {
+    parsed.is_some_and(|p| {
+        !p.active_windows.is_empty()
+            && p.active_windows
+                .iter()
+                .all(|w| w.end.is_some_and(|end| end < now))
+    })
+}
+
+fn in_force(parsed: Option<&ParsedTFREvent>, now: DateTime<Utc>) -> bool {
+    parsed.is_some_and(|p| p.active_windows.iter().any(|w| w.overlaps(now, now)))
+}
+
+/// Feed fields that differ between two downloads of the same NOTAM
+pub fn changed_fields<'a>(old: &RawTFREvent, new: &RawTFREvent) -> Vec<&'a str> {
+    let mut fields = Vec::new();
+    if old.description != new.description {
+        fields.push("description");
+    }
+    if old.location != new.location {
+        fields.push("location");
+    }
+    if old.r#type != new.r#type {
+        fields.push("type");
+    }
+    // fields a cache from before we kept them doesn't have aren't a change
+    let differs = |o: &Option<String>, n: &Option<String>| o.is_some() && o != n;
+    if differs(&old.state, &new.state) || differs(&old.facility, &new.facility) {
+        fields.push("state/facility");
+    }
+    if differs(&old.mod_date, &new.mod_date) || differs(&old.mod_abs_time, &new.mod_abs_time) {
+        fields.push("modification time");
+    }
+    fields
+}
+
+/// Changes between the `previous` and `current` downloads, given each
+/// TFR's last recorded state and the TFRs that have already been reported
+/// `activated`. `listed` is every ID in the current feed, so a TFR our type
+/// filter dropped isn't taken for a cancelled one; `updated` has the TFRs
+/// whose re-fetched details differ.
+pub fn diff_snapshots(
+    previous: &[RawTFREvent],
+    current: &[RawTFREvent],
+    listed: &HashSet<&str>,
+    updated: &HashMap<String, Vec<FieldChange>>,
+    states: &HashMap<String, LifecycleState>,
+    activated: &HashSet<String>,
+    now: DateTime<Utc>,
+) -> Vec<LifecycleChange> {
+    let before: HashMap<_, _> = previous.iter().map(|e| (e.notam_id.as_str(), e)).collect();
+    let change = |event: &RawTFREvent, state, detail: String| LifecycleChange {
+        notam_id: event.notam_id.clone(),
+        state,
+        at: now,
+        location: event
+            .parsed
+            .as_ref()
+            .map(|p| p.location.clone())
+            .or_else(|| event.location.clone())
+            .unwrap_or_default(),
+        detail,
+        changes: Vec::new(),
+    };
+
+    let mut changes = Vec::new();
+    for event in current {
+        let last = states.get(&event.notam_id).copied();
+        let parsed = event.parsed.as_ref();
+        let Some(old) = before.get(event.notam_id.as_str()) else {
+            changes.push(change(event, LifecycleState::New, String::new()));
+            continue;
+        };
+
+        let details = updated.get(&event.notam_id).map_or(&[][..], Vec::as_slice);
+        let mut fields = changed_fields(old, event);
+        for c in details {
+            if !fields.contains(&c.field.as_str()) {
+                fields.push(&c.field);
+            }
+        }
+        if !fields.is_empty() {
+            let detail = format!("{} changed", fields.join(", "));
+            changes.push(LifecycleChange {
+                changes: details.to_vec(),
+                ..change(event, LifecycleState::Modified, detail)
+            });
+        }
+        // an edit doesn't stand in for going into force, before or after
+        if has_ended(parsed, now) {
+            if last != Some(LifecycleState::Expired) && fields.is_empty() {
+                changes.push(change(event, LifecycleState::Expired, String::new()));
+            }
+        } else if in_force(parsed, now) && !activated.contains(&event.notam_id) {
+            changes.push(change(event, LifecycleState::Active, String::new()));
+        }
+    }
+
+    let current_ids: HashSet<_> = current.iter().map(|e| e.notam_id.as_str()).collect();
+    for old in previous {
+        let id = old.notam_id.as_str();
+        if current_ids.contains(id)
+            || listed.contains(id)
+            || states.get(id).is_some_and(LifecycleState::is_final)
+        {
+            continue;
+        }
+        let state = if has_ended(old.parsed.as_ref(), now) {
+            LifecycleState::Expired
+        } else {
+            LifecycleState::Cancelled
+        };
+        changes.push(change(old, state, String::new()));
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schedule::ActiveWindow;
+    use chrono::{Duration, TimeZone};
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 10, 3, 12, 0, 0).unwrap()
+    }
+
+    /// In force from `start` to `end` hours from now
+    fn raw(id: &str, start: i64, end: i64) -> RawTFREvent {
+        RawTFREvent {
+            notam_id: id.to_string(),
+            description: format!("{id} description"),
+            r#type: Some("SECURITY".to_string()),
+            parsed: Some(ParsedTFREvent {
+                notam_id: id.to_string(),
+                active_windows: vec![ActiveWindow {
+                    start: now() + Duration::hours(start),
+                    end: Some(now() + Duration::hours(end)),
+                }],
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn diff(
+        previous: &[RawTFREvent],
+        current: &[RawTFREvent],
+        states: &[(&str, LifecycleState)],
+    ) -> Vec<(String, LifecycleState)> {
+        let listed = current.iter().map(|e| e.notam_id.as_str()).collect();
+        // as if an Active state is the only one there's been
+        let activated = states
+            .iter()
+            .filter(|(_, s)| *s == LifecycleState::Active)
+            .map(|(id, _)| id.to_string())
+            .collect();
+        let states = states.iter().map(|(id, s)| (id.to_string(), *s)).collect();
+        diff_snapshots(
+            previous,
+            current,
+            &listed,
+            &HashMap::new(),
+            &states,
+            &activated,
+            now(),
+        )
+        .into_iter()
+        .map(|c| (c.notam_id, c.state))
+        .collect()
+    }
+
+    #[test]
+    fn new_then_active() {
+        let upcoming = vec![raw("5/1", 1, 5)];
+        assert_eq!(
+            diff(&[], &upcoming, &[]),
+            [("5/1".to_string(), LifecycleState::New)]
+        );
+        // not started yet
+        assert!(diff(&upcoming, &upcoming, &[("5/1", LifecycleState::New)]).is_empty());
+
+        let started = vec![raw("5/1", -1, 5)];
+        assert_eq!(
+            diff(&started, &started, &[("5/1", LifecycleState::New)]),
+            [("5/1".to_string(), LifecycleState::Active)]
+        );
+        assert!(diff(&started, &started, &[("5/1", LifecycleState::Active)]).is_empty());
+    }
+
+    #[test]
+    fn modified() {
+        let old = raw("5/1", -1, 5);
+        let mut new = old.clone();
+        new.description = "moved".to_string();
+        assert_eq!(
+            diff(&[old], &[new], &[("5/1", LifecycleState::Active)]),
+            [("5/1".to_string(), LifecycleState::Modified)]
+        );
+    }
+
+    #[test]
+    fn modified_before_it_starts_still_goes_active() {
+        let upcoming = raw("5/1", 1, 5);
+        let mut edited = upcoming.clone();
+        edited.description = "moved".to_string();
+        let listed = HashSet::from(["5/1"]);
+        let mut states = HashMap::new();
+        let mut activated = HashSet::new();
+        let mut step = |previous: &RawTFREvent, current: &RawTFREvent, at| {
+            let changes = diff_snapshots(
+                std::slice::from_ref(previous),
+                std::slice::from_ref(current),
+                &listed,
+                &HashMap::new(),
+                &states,
+                &activated,
+                at,
+            );
+            for c in &changes {
+                states.insert(c.notam_id.clone(), c.state);
+                if c.state == LifecycleState::Active {
+                    activated.insert(c.notam_id.clone());
+                }
+            }
+            changes.into_iter().map(|c| c.state).collect::<Vec<_>>()
+        };
+
+        // published as New, then edited before it starts
+        assert_eq!(step(&upcoming, &edited, now()), [LifecycleState::Modified]);
+        let started = now() + Duration::hours(2);
+        assert_eq!(step(&edited, &edited, started), [LifecycleState::Active]);
+        assert!(step(&edited, &edited, started).is_empty());
+        // edited again while in force: modified, but not active twice
+        let mut again = edited.clone();
+        again.description = "moved again".to_string();
+        assert_eq!(step(&edited, &again, started), [LifecycleState::Modified]);
+    }
+
+    #[test]
+    fn edited_as_it_starts_is_both() {
+        let old = raw("5/1", -1, 5);
+        let mut new = old.clone();
+        new.description = "moved".to_string();
+        assert_eq!(
+            diff(&[old], &[new], &[("5/1", LifecycleState::Modified)]),
+            [
+                ("5/1".to_string(), LifecycleState::Modified),
+                ("5/1".to_string(), LifecycleState::Active),
+            ]
+        );
+    }
+
+    #[test]
+    fn removed_early_is_cancelled_and_late_is_expired() {
+        let running = raw("5/1", -1, 5);
+        let over = raw("5/2", -5, -1);
+        assert_eq!(
+            diff(&[running, over], &[], &[]),
+            [
+                ("5/1".to_string(), LifecycleState::Cancelled),
+                ("5/2".to_string(), LifecycleState::Expired),
+            ]
+        );
+    }
+
+    #[test]
+    fn expired_while_still_listed() {
+        let over = vec![raw("5/1", -5, -1)];
+        assert_eq!(
+            diff(&over, &over, &[("5/1", LifecycleState::Active)]),
+            [("5/1".to_string(), LifecycleState::Expired)]
+        );
+        // and not again once it drops off the feed
+        assert!(diff(&over, &[], &[("5/1", LifecycleState::Expired)]).is_empty());
+    }
+
+    #[test]
+    fn filtered_out_is_not_cancelled() {
+        let old = raw("5/1", -1, 5);
+        let listed = HashSet::from(["5/1"]);
+        let changes = diff_snapshots(
+            &[old],
+            &[],
+            &listed,
+            &HashMap::new(),
+            &HashMap::new(),
+            &HashSet::new(),
+            now(),
+        );
+        assert!(changes.is_empty());
+    }
+
+    #[test]
+    fn replacement_links_back_with_a_diff() {
+        let first = ParsedTFREvent {
+            notam_id: "5/1".to_string(),
+            end: "October 03, 2025 at 2300 UTC".to_string(),
+            reason: "VIP MOVEMENT".to_string(),
+            ..Default::default()
+        };
+        let second = ParsedTFREvent {
+            notam_id: "5/2".to_string(),
+            replaced: "5/1".to_string(),
+            ..first.clone()
+        };
+        let mut third = ParsedTFREvent {
+            notam_id: "5/3".to_string(),
+            end: "October 04, 2025 at 0100 UTC".to_string(),
+            replaced: "5/2 , N/A".to_string(),
+            ..first.clone()
+        };
+        assert_eq!(third.replaced_ids(), ["5/2"]);
+
+        let mut second = second;
+        second.follow(&first);
+        assert!(second.field_changes.is_empty());
+        third.follow(&second);
+        assert_eq!(third.supersedes, ["5/1", "5/2"]);
+        assert_eq!(third.field_changes.len(), 1);
+        assert_eq!(
+            third.field_changes[0].to_string(),
+            "end: October 03, 2025 at 2300 UTC → October 04, 2025 at 0100 UTC"
+        );
+    }
+
+    #[test]
+    fn changed_details_are_modified() {
+        let feed = vec![raw("5/1", -1, 5)];
+        let updated = HashMap::from([(
+            "5/1".to_string(),
+            vec![FieldChange {
+                field: "end".to_string(),
+                old: "2300".to_string(),
+                new: "0100".to_string(),
+            }],
+        )]);
+        let listed = HashSet::from(["5/1"]);
+        let states = HashMap::from([("5/1".to_string(), LifecycleState::Active)]);
+        let activated = HashSet::from(["5/1".to_string()]);
+        let changes = diff_snapshots(&feed, &feed, &listed, &updated, &states, &activated, now());
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].state, LifecycleState::Modified);
+        assert_eq!(changes[0].detail, "end changed");
+        assert_eq!(changes[0].changes, updated["5/1"]);
+    }
+}
@@ -6,28 +6,75 @@
 
 // You should have received a copy of the GNU General Public License along with TFRAlert. If not, see <https://www.gnu.org/licenses/>.
 
+use crate::altitude::{self, AltitudeBand};
+use crate::archive::{self, Recorder};
+use crate::config::Config;
+use crate::detail::{self, ParseWarning};
+use crate::fingerprint;
+use crate::geo::{Shape, WatchLocation};
+use crate::keywords::KeywordRules;
+use crate::lifecycle::{self, FieldChange, LifecycleChange, LifecycleState};
+use crate::rules::MatchRule;
+use crate::schedule::{self, ActiveWindow};
+use crate::source::{FeedSource, HttpSource, ReplaySource};
+use crate::store::TfrStore;
 use anyhow::Result;
-use log::{debug, error, info};
-use reqwest::Client;
-use scraper::{Html, Selector};
-use serde::{Deserialize, Serialize};
-use std::{collections::HashSet, fs, path::PathBuf};
-
-const RAW_EVENT_CACHE: &str = "tfr_cache.json";
-const MATCHED_EVENT_CACHE: &str = "tfr_matches.json";
-
-const JSON_FEED_TFR_URL: &str = "https://tfr.faa.gov/tfrapi/exportTfrList";
-pub const NOTAM_DETAIL_URL: &str = "https://tfr.faa.gov/tfrapi/getWebText?notamId=";
-// todo customization
-const ALTITUDE_PARAMS: &str = "up to and including 400 feet AGL";
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
+use chrono::{DateTime, Utc};
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+    path::PathBuf,
+};
+
+/// Part 107 limit
+pub const DEFAULT_OPERATING_CEILING_FT: u32 = 400;
+
+/// One entry of the FAA's `exportTfrList` feed, plus our parsed details.
+/// Fields the FAA adds later land in `extra`, so re-saving the cache keeps
+/// them.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct RawTFREvent {
     pub notam_id: String,
+    #[serde(default)]
     pub description: String,
+    #[serde(default, deserialize_with = "lenient_string")]
     pub location: Option<String>,
+    #[serde(default, deserialize_with = "lenient_string")]
     pub r#type: Option<String>,
+    /// Issuing facility (ARTCC), e.g. "ZDC"
+    #[serde(default, deserialize_with = "lenient_string")]
+    pub facility: Option<String>,
+    /// Two-letter state, e.g. "MD"
+    #[serde(default, deserialize_with = "lenient_string")]
+    pub state: Option<String>,
+    #[serde(default, deserialize_with = "lenient_string")]
+    pub creation_date: Option<String>,
+    /// When the FAA last changed the NOTAM, as published
+    #[serde(default, deserialize_with = "lenient_string")]
+    pub mod_date: Option<String>,
+    #[serde(default, deserialize_with = "lenient_string")]
+    pub mod_abs_time: Option<String>,
+    #[serde(default, deserialize_with = "lenient_string")]
+    pub gid: Option<String>,
+    #[serde(default, deserialize_with = "lenient_string")]
+    pub is_new: Option<String>,
+    #[serde(default)]
     pub parsed: Option<ParsedTFREvent>,
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, serde_json::Value>,
+}
+
+/// A string field that may also come as a number or bool, so a change in
+/// how the FAA encodes one field doesn't lose us the whole feed
+fn lenient_string<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    use serde_json::Value;
+    Ok(match Option::<Value>::deserialize(deserializer)? {
+        None | Some(Value::Null) => None,
+        Some(Value::String(s)) => Some(s),
+        Some(other) => Some(other.to_string()),
+    })
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -44,76 +91,375 @@ pub struct ParsedTFREvent {
     pub restrictions: String,
     pub other_info: String,
     pub description: String,
+    /// The NOTAM as published, when the detail page includes it
+    #[serde(default)]
+    pub notam_text: String,
+    /// Why our rules picked this TFR, e.g. "keyword 'stadium'"
+    #[serde(default)]
+    pub match_reasons: Vec<String>,
+    /// `begin`/`end`/effective dates as UTC; `schedule_error` says why not
+    #[serde(default)]
+    pub active_windows: Vec<ActiveWindow>,
+    #[serde(default)]
+    pub schedule_error: Option<String>,
+    /// Earlier NOTAMs for the same TFR that this one replaced, oldest first
+    #[serde(default)]
+    pub supersedes: Vec<String>,
+    /// What changed from the NOTAM this one replaced
+    #[serde(default)]
+    pub field_changes: Vec<FieldChange>,
+    /// The newer NOTAM that replaced this one
+    #[serde(default)]
+    pub superseded_by: Option<String>,
+    /// When the detail page was downloaded
+    #[serde(default)]
+    pub fetched_at: Option<DateTime<Utc>>,
+    /// Fields of the detail page that were missing or couldn't be read
+    #[serde(default)]
+    pub parse_warnings: Vec<ParseWarning>,
+    /// How much of the detail page we understood, 0 to 100; `None` for
+    /// events parsed before we kept score
+    #[serde(default)]
+    pub parse_quality: Option<u8>,
 }
 
+impl ParsedTFREvent {
+    /// Fill in `active_windows` (or `schedule_error`) from the raw strings
+    pub fn resolve_schedule(&mut self) {
+        match schedule::active_windows(&self.begin, &self.end, &self.airspace.effective) {
+            Ok(windows) => {
+                self.active_windows = windows;
+                self.schedule_error = None;
+            }
+            Err(e) => {
+                warn!("Schedule for {}: {}", self.notam_id, e);
+                self.active_windows = Vec::new();
+                self.schedule_error = Some(e.to_string());
+            }
+        }
+    }
+
+    /// NOTAM IDs listed under "Replaced NOTAM(s)", e.g. "5/1234"
+    pub fn replaced_ids(&self) -> Vec<String> {
+        self.replaced
+            .split(|c: char| !(c.is_ascii_digit() || c == '/'))
+            .filter(|token| {
+                token.split_once('/').is_some_and(|(year, number)| {
+                    !year.is_empty()
+                        && !number.is_empty()
+                        && !number.contains('/')
+                        && year.chars().all(|c| c.is_ascii_digit())
+                })
+            })
+            .filter(|id| *id != self.notam_id)
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Link this NOTAM to the one it replaced: the chain of earlier IDs and
+    /// what changed since
+    pub fn follow(&mut self, previous: &ParsedTFREvent) {
+        self.supersedes = previous.supersedes.clone();
+        self.supersedes.push(previous.notam_id.clone());
+        self.field_changes = lifecycle::diff_events(previous, self);
+    }
+
+    /// Work out the typed fields that events cached by older versions
+    /// don't have yet
+    pub fn resolve_missing(&mut self) {
+        for area in self.airspace.areas.iter_mut() {
+            if area.shape.is_none() && area.geometry_error.is_none() {
+                area.resolve_geometry();
+            }
+            if area.altitude_band.is_none() && area.altitude_error.is_none() {
+                area.resolve_altitude();
+            }
+        }
+        if self.active_windows.is_empty() && self.schedule_error.is_none() {
+            self.resolve_schedule();
+        }
+    }
+}
+
+/// All the areas listed under "Airspace Definition", plus when they apply.
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Airspace {
+    #[serde(default)]
+    pub areas: Vec<AirspaceArea>,
+    pub effective: Vec<String>,
+}
+
+/// One circle or polygon with its own altitude band.
+///
+/// `center`, `radius` and `boundary` keep the text as published; `shape` is
+/// the numeric version, or `geometry_error` says why there isn't one.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AirspaceArea {
     pub center: String,
     pub radius: String,
+    #[serde(default)]
+    pub boundary: String,
     pub altitude: String,
-    pub effective: Vec<String>,
+    #[serde(default)]
+    pub shape: Option<Shape>,
+    #[serde(default)]
+    pub geometry_error: Option<String>,
+    #[serde(default)]
+    pub altitude_band: Option<AltitudeBand>,
+    #[serde(default)]
+    pub altitude_error: Option<String>,
 }
 
-// Trait - they can both be represented as json
-pub trait TFREvent: Serialize + for<'de> Deserialize<'de> + std::fmt::Debug {}
-impl TFREvent for ParsedTFREvent {}
-impl TFREvent for RawTFREvent {}
+impl AirspaceArea {
+    pub(crate) fn has_geometry(&self) -> bool {
+        !self.center.is_empty() || !self.boundary.is_empty()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        !self.has_geometry() && self.radius.is_empty() && self.altitude.is_empty()
+    }
+
+    /// Fill in `shape` (or `geometry_error`) from the raw strings
+    pub fn resolve_geometry(&mut self) {
+        if self.center.is_empty() && self.radius.is_empty() && self.boundary.is_empty() {
+            return;
+        }
+        match Shape::parse(&self.center, &self.radius, &self.boundary) {
+            Ok(shape) => {
+                self.shape = Some(shape);
+                self.geometry_error = None;
+            }
+            Err(e) => {
+                warn!("Airspace geometry: {}", e);
+                self.shape = None;
+                self.geometry_error = Some(e.to_string());
+            }
+        }
+    }
 
-pub async fn download_json_feed(client: &Client) -> Result<Vec<RawTFREvent>> {
-    let resp = client.get(JSON_FEED_TFR_URL).send().await?;
-    let json = resp.json::<Vec<RawTFREvent>>().await?;
-    Ok(json)
+    /// Fill in `altitude_band` (or `altitude_error`) from the raw string
+    pub fn resolve_altitude(&mut self) {
+        if self.altitude.is_empty() {
+            return;
+        }
+        match altitude::parse_altitude(&self.altitude) {
+            Ok(band) => {
+                self.altitude_band = Some(band);
+                self.altitude_error = None;
+            }
+            Err(e) => {
+                warn!("Airspace altitude: {}", e);
+                self.altitude_band = None;
+                self.altitude_error = Some(e.to_string());
+            }
+        }
+    }
 }
 
-pub fn load_raw_cache() -> Vec<RawTFREvent> {
-    read_cache_file(RAW_EVENT_CACHE)
+impl Airspace {
+    /// Typed geometry and altitude for every area
+    pub fn resolve(&mut self) {
+        for area in self.areas.iter_mut() {
+            area.resolve_geometry();
+            area.resolve_altitude();
+        }
+    }
+
+    /// First geometry problem, if any area couldn't be placed
+    pub fn geometry_error(&self) -> Option<&str> {
+        self.areas.iter().find_map(|a| a.geometry_error.as_deref())
+    }
+
+    /// First altitude problem, if any area's altitude couldn't be read
+    pub fn altitude_error(&self) -> Option<&str> {
+        self.areas.iter().find_map(|a| a.altitude_error.as_deref())
+    }
+
+    /// Raw location and altitude text of every area, for keyword search
+    pub fn text(&self) -> String {
+        self.areas
+            .iter()
+            .map(|a| format!("{} {} {} {}", a.center, a.radius, a.boundary, a.altitude))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
 }
 
-pub fn save_raw_cache(data: &[RawTFREvent]) -> Result<()> {
-    write_cache_file(RAW_EVENT_CACHE, data)
+/// Which TFR types (or states, or facilities) from the feed we care about,
+/// compared case-insensitively. An empty include list means all of them.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(from = "TypeFilterRepr")]
+pub struct TypeFilter {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
 }
 
-pub fn load_matched_cache() -> Vec<ParsedTFREvent> {
-    read_cache_file(MATCHED_EVENT_CACHE)
+/// `types = ["SECURITY"]` is shorthand for `include`
+#[derive(Deserialize)]
+#[serde(untagged, deny_unknown_fields)]
+enum TypeFilterRepr {
+    Include(Vec<String>),
+    Full {
+        #[serde(default)]
+        include: Vec<String>,
+        #[serde(default)]
+        exclude: Vec<String>,
+    },
 }
 
-pub fn save_matched_cache(data: &[ParsedTFREvent]) -> Result<()> {
-    write_cache_file(MATCHED_EVENT_CACHE, data)
+impl From<TypeFilterRepr> for TypeFilter {
+    fn from(repr: TypeFilterRepr) -> Self {
+        match repr {
+            TypeFilterRepr::Include(include) => TypeFilter {
+                include,
+                exclude: Vec::new(),
+            },
+            TypeFilterRepr::Full { include, exclude } => TypeFilter { include, exclude },
+        }
+    }
 }
 
-fn read_cache_file<T: TFREvent>(path: &str) -> Vec<T> {
-    let path = PathBuf::from(path);
-    if path.exists() {
-        match fs::read_to_string(&path) {
-            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
-                error!("Failed to parse JSON from {}: {}", path.display(), e);
-                Vec::new()
-            }),
-            Err(e) => {
-                error!("Failed to read cache file {}: {}", path.display(), e);
-                Vec::new()
-            }
+impl TypeFilter {
+    pub fn matches(&self, event_type: &str) -> bool {
+        let event_type = event_type.trim();
+        let listed = |list: &[String]| {
+            list.iter()
+                .any(|t| event_type.eq_ignore_ascii_case(t.trim()))
+        };
+        (self.include.is_empty() || listed(&self.include)) && !listed(&self.exclude)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    /// e.g. "SECURITY, VIP" or "all except HAZARDS"
+    pub fn describe(&self) -> String {
+        let include = if self.include.is_empty() {
+            "all".to_string()
+        } else {
+            self.include.join(", ")
+        };
+        if self.exclude.is_empty() {
+            include
+        } else {
+            format!("{} except {}", include, self.exclude.join(", "))
         }
-    } else {
-        Vec::new()
     }
 }
 
-fn write_cache_file<T: TFREvent>(path: &str, data: &[T]) -> Result<()> {
-    let serialized = serde_json::to_string_pretty(data)?;
-    fs::write(path, serialized)?;
-    Ok(())
+/// What a TFR has to look like for us to care about it.
+///
+/// Whatever a TFR's page doesn't let us check (no airspace, an unparsed
+/// location, altitude or schedule) passes that check, since dropping a real
+/// TFR is worse than a spurious alert.
+#[derive(Debug, Clone, Default)]
+pub struct MatchCriteria {
+    pub types: TypeFilter,
+    pub states: TypeFilter,
+    pub facilities: TypeFilter,
+    pub keywords: KeywordRules,
+    pub locations: Vec<WatchLocation>,
+    /// How high we fly, feet AGL
+    pub operating_ceiling_ft: u32,
+    /// Ground elevation (feet MSL) used to compare MSL and FL limits
+    pub ground_elevation_ft: u32,
+    /// Only TFRs active within this many hours from now; 0 for any time
+    pub lookahead_hours: u32,
+    /// Match as of this time instead of now, to replay a capture
+    pub as_of: Option<DateTime<Utc>>,
 }
 
-/// event type "SECURITY"
-pub fn get_filtered_events(data: &[RawTFREvent]) -> Vec<RawTFREvent> {
-    data.iter()
-        .filter(|e| {
-            e.r#type
+impl MatchCriteria {
+    /// Whether a feed entry passes the type, state and facility filters.
+    /// Entries without a state or facility are kept.
+    pub fn passes_feed(&self, event: &RawTFREvent) -> bool {
+        self.types.matches(event.r#type.as_deref().unwrap_or(""))
+            && event
+                .state
                 .as_deref()
-                .unwrap_or("")
-                .eq_ignore_ascii_case("SECURITY")
-        })
+                .is_none_or(|s| self.states.matches(s))
+            && event
+                .facility
+                .as_deref()
+                .is_none_or(|f| self.facilities.matches(f))
+    }
+
+    /// keywords AND altitude AND location AND time
+    pub fn rule(&self) -> MatchRule {
+        let mut all = Vec::new();
+        if !self.keywords.include.is_empty() {
+            all.push(MatchRule::Any(
+                self.keywords
+                    .include
+                    .iter()
+                    .cloned()
+                    .map(MatchRule::Keyword)
+                    .collect(),
+            ));
+        }
+        if !self.keywords.exclude.is_empty() {
+            all.push(MatchRule::Not(Box::new(MatchRule::Any(
+                self.keywords
+                    .exclude
+                    .iter()
+                    .cloned()
+                    .map(MatchRule::Keyword)
+                    .collect(),
+            ))));
+        }
+        all.push(MatchRule::Altitude {
+            operating_ceiling_ft: self.operating_ceiling_ft,
+            ground_elevation_ft: self.ground_elevation_ft,
+        });
+        if !self.locations.is_empty() {
+            all.push(MatchRule::Any(
+                self.locations
+                    .iter()
+                    .cloned()
+                    .map(MatchRule::Near)
+                    .collect(),
+            ));
+        }
+        if self.lookahead_hours > 0 {
+            all.push(MatchRule::ActiveWithin {
+                hours: self.lookahead_hours,
+                now: self.as_of,
+            });
+        }
+        MatchRule::All(all)
+    }
+
+    /// One-line summary of the active filters, for the UI
+    pub fn describe(&self) -> String {
+        let mut parts = vec![
+            format!("type: {}", self.types.describe()),
+            format!("altitude: 0–{} ft AGL", self.operating_ceiling_ft),
+        ];
+        if !self.states.is_empty() {
+            parts.push(format!("state: {}", self.states.describe()));
+        }
+        if !self.facilities.is_empty() {
+            parts.push(format!("facility: {}", self.facilities.describe()));
+        }
+        if !self.keywords.is_empty() {
+            parts.push(format!("{} keyword(s)", self.keywords.len()));
+        }
+        if !self.locations.is_empty() {
+            let names: Vec<_> = self.locations.iter().map(|l| l.name.as_str()).collect();
+            parts.push(format!("near {}", names.join(", ")));
+        }
+        if self.lookahead_hours > 0 {
+            parts.push(format!("active within {} h", self.lookahead_hours));
+        }
+        parts.join(", ")
+    }
+}
+
+/// events whose type, state and facility pass the filters
+pub fn get_filtered_events(data: &[RawTFREvent], criteria: &MatchCriteria) -> Vec<RawTFREvent> {
+    data.iter()
+        .filter(|e| criteria.passes_feed(e))
         .cloned()
         .collect()
 }
@@ -128,217 +474,325 @@ pub fn get_new_events(current: &[RawTFREvent], cached: &[RawTFREvent]) -> Vec<Ra
         .collect()
 }
 
-/// NOTAM detail page
-pub async fn fetch_detail_page(client: &Client, notam_id: &str) -> Result<String> {
-    let url = format!("{}{}", NOTAM_DETAIL_URL, notam_id);
-    let resp = client.get(&url).send().await?.text().await?;
-    Ok(resp)
-}
-
-/// keyword search from text file (TODO)
+/// keyword rules from a text file, one per line (see `keywords`)
 pub fn load_keywords(path: Option<&str>) -> Vec<String> {
     if let Some(path) = path {
         debug!("Load keywords...");
         let p = PathBuf::from(path);
         if p.exists() {
-            if let Ok(content) = fs::read_to_string(p) {
-                return content
-                    .lines()
-                    .map(|s| s.trim().to_lowercase())
-                    .filter(|s| !s.is_empty())
-                    .collect();
+            match fs::read_to_string(&p) {
+                Ok(content) => {
+                    return content
+                        .lines()
+                        .map(|s| s.trim().to_string())
+                        .filter(|s| !s.is_empty())
+                        .collect();
+                }
+                Err(e) => error!("Failed to read keyword file {}: {}", p.display(), e),
             }
         }
     }
     Vec::new()
 }
 
-// pub fn event_matches_criteria(html_text: &str, keywords: &[String]) -> bool {
-//     if keywords.is_empty() {
-//         return true;
-//     }
-//     let lower = html_text.to_lowercase();
-//     keywords.iter().any(|kw| lower.contains(kw))
-// }
+/// Run the match rules over a parsed NOTAM, recording why it matched
+pub fn match_event(parsed: &mut ParsedTFREvent, criteria: &MatchCriteria) -> bool {
+    parsed.resolve_missing();
+    let outcome = criteria.rule().evaluate(parsed);
+    debug!(
+        "{} {}: {}",
+        parsed.notam_id,
+        if outcome.matched {
+            "matched"
+        } else {
+            "skipped"
+        },
+        outcome.reasons.join("; ")
+    );
+    parsed.match_reasons = if outcome.matched {
+        outcome.reasons
+    } else {
+        Vec::new()
+    };
+    outcome.matched
+}
+
+/// What one check of the feed found
+#[derive(Debug, Clone, Default)]
+pub struct CheckResult {
+    pub new_matches: Vec<ParsedTFREvent>,
+    /// What happened since the last check to TFRs we had already matched
+    pub changes: Vec<LifecycleChange>,
+}
+
+/// Known TFRs re-fetched per check at most, besides those whose feed entry
+/// changed, to go easy on tfr.faa.gov
+const MAX_REFETCHES_PER_CHECK: usize = 20;
+
+/// Known TFRs to download details for again: the feed entry changed, we
+/// never got details, or they're older than `refresh_hours` (0 for never)
+fn due_for_refetch(
+    current: &[RawTFREvent],
+    cached: &[RawTFREvent],
+    refresh_hours: u32,
+    now: DateTime<Utc>,
+) -> Vec<RawTFREvent> {
+    let cached: HashMap<_, _> = cached.iter().map(|e| (e.notam_id.as_str(), e)).collect();
+    let mut changed = Vec::new();
+    let mut stale = Vec::new();
+    for event in current {
+        let Some(old) = cached.get(event.notam_id.as_str()) else {
+            continue;
+        };
+        if fingerprint::feed_entry_changed(old, event) {
+            changed.push(event.clone());
+            continue;
+        }
+        let fetched_at = old.parsed.as_ref().map(|p| p.fetched_at);
+        let due = match fetched_at {
+            None => true,
+            Some(_) if refresh_hours == 0 => false,
+            Some(None) => true,
+            Some(Some(at)) => now - at >= chrono::Duration::hours(refresh_hours as i64),
+        };
+        if due {
+            stale.push((fetched_at.flatten(), event.clone()));
+        }
+    }
+    // oldest details first
+    stale.sort_by_key(|(at, _)| *at);
+    changed.extend(
+        stale
+            .into_iter()
+            .take(MAX_REFETCHES_PER_CHECK)
+            .map(|(_, e)| e),
+    );
+    changed
+}
 
-async fn process_feed(keywords: &[String]) -> Result<Vec<ParsedTFREvent>> {
+pub(crate) async fn process_feed(
+    source: &impl FeedSource,
+    store: &mut dyn TfrStore,
+    criteria: &MatchCriteria,
+    detail_refresh_hours: u32,
+) -> Result<CheckResult> {
     use log::{debug, error, info};
 
-    let client = Client::new();
+    let now = criteria.as_of.unwrap_or_else(Utc::now);
 
     debug!("Check feed");
-    let current_data = download_json_feed(&client).await?;
+    let current_data = source.feed().await?;
     info!("Downloaded {} total items", current_data.len());
 
-    let cached_data = load_raw_cache();
-    let current = get_filtered_events(&current_data);
-    let cached = get_filtered_events(&cached_data);
+    let cached_data = store.load_feed()?;
+    let current = get_filtered_events(&current_data, criteria);
+    let cached = get_filtered_events(&cached_data, criteria);
     let new_events = get_new_events(&current, &cached);
+    let refetch = due_for_refetch(&current, &cached, detail_refresh_hours, now);
+
+    // keep details alongside the raw feed so filters can be re-run offline
+    let mut cached_details: HashMap<_, _> = cached
+        .iter()
+        .filter_map(|e| e.parsed.clone().map(|p| (e.notam_id.clone(), p)))
+        .collect();
 
-    let mut matched_cache = load_matched_cache();
+    let matched_ids = store.match_ids()?;
     let mut new_matches = Vec::new();
+    let mut fetched = HashMap::new();
+    // (replaced, replacement) pairs
+    let mut superseded = Vec::new();
 
     if !new_events.is_empty() {
         info!("Found {} new event(s)", new_events.len());
 
-        for mut event in new_events {
+        for event in new_events {
             debug!("Processing NOTAM id {}", event.notam_id);
-            match fetch_detail_page(&client, &event.notam_id).await {
+            match source.detail_page(&event.notam_id).await {
                 Ok(html) => {
-                    let parsed = parse_notam_html(&html);
-                    event.parsed = Some(parsed.clone());
-
-                    let searchable_text = format!(
-                        "{} {} {} {} {} {} {} {} {} {}",
-                        parsed.notam_id.clone(),
-                        parsed.location,
-                        parsed.reason,
-                        parsed.begin,
-                        parsed.end,
-                        parsed.restrictions,
-                        parsed.other_info,
-                        parsed.airspace.center,
-                        parsed.airspace.altitude,
-                        parsed.description
-                    )
-                    .to_lowercase();
-
-                    if keywords.is_empty()
-                        || keywords.iter().any(|kw| {
-                            searchable_text.contains(kw)
-                                || searchable_text.contains(ALTITUDE_PARAMS)
-                        })
+                    let mut parsed = detail::parse_notam_html(&html);
+                    parsed.fetched_at = Some(now);
+                    for old_id in parsed.replaced_ids() {
+                        if let Some(old) = store.find_event(&old_id)? {
+                            info!("{} replaces {}", parsed.notam_id, old_id);
+                            parsed.follow(&old);
+                            superseded.push((old, parsed.notam_id.clone()));
+                        }
+                    }
+
+                    if match_event(&mut parsed, criteria) && !matched_ids.contains(&parsed.notam_id)
                     {
                         info!("Event matches criteria: {}", event.notam_id);
-                        new_matches
-                            .push(event.parsed.as_ref().expect("Need parsed TFR data").clone());
-                        matched_cache.push(event.parsed.expect("Need parsed TFR data").clone());
+                        new_matches.push(parsed.clone());
                     }
+                    fetched.insert(event.notam_id, parsed);
                 }
                 Err(e) => error!("Error processing event {}: {}", event.notam_id, e),
             }
         }
     }
 
-    save_raw_cache(&current)?;
-    save_matched_cache(&matched_cache)?;
-    info!("Cache updated");
-    Ok(new_matches)
-}
-
-pub async fn check_feed() -> Result<Vec<ParsedTFREvent>> {
-    debug!("Check feed...");
-    // TODO
-    let keywords = load_keywords(None);
-    process_feed(&keywords).await
-}
-
-fn extract_text(element: &scraper::ElementRef) -> String {
-    element
-        .text()
-        .map(|t| t.trim().replace("\\r", "").replace("\\n", ""))
-        .filter(|t| !t.is_empty())
-        .collect::<Vec<_>>()
-        .join(" ")
-}
-
-/// Parses detailed NOTAM HTML into structured NotamDetail.
-pub fn parse_notam_html(html_text: &str) -> ParsedTFREvent {
-    let document = Html::parse_document(html_text);
-    let table_selector = Selector::parse("table").unwrap();
-    let tr_selector = Selector::parse("tr").unwrap();
-    let td_selector = Selector::parse("td").unwrap();
-
-    let mut detail = ParsedTFREvent::default();
-
-    for table in document.select(&table_selector) {
-        let table_text = extract_text(&table);
-
-        // Metadata table (date, notam id)
-        if table_text.contains("Issue Date") {
-            if table_text.contains("NOTAM Number") {
-                if let Some(first_row) = table.select(&tr_selector).next() {
-                    let tds: Vec<_> = first_row.select(&td_selector).collect();
-                    if let Some(last) = tds.last() {
-                        let font_selector = Selector::parse("font").unwrap();
-                        if let Some(font) = last.select(&font_selector).next() {
-                            detail.notam_id = extract_text(&font)
-                                .replace("FDC", "")
-                                .trim_ascii()
-                                .to_string();
-                        }
-                    }
-                }
+    // details that changed since we last fetched them, by notam_id
+    let mut updated = HashMap::new();
+    if !refetch.is_empty() {
+        debug!("Re-fetching {} known event(s)", refetch.len());
+    }
+    for event in refetch {
+        let html = match source.detail_page(&event.notam_id).await {
+            Ok(html) => html,
+            Err(e) => {
+                error!("Error re-fetching event {}: {}", event.notam_id, e);
+                continue;
             }
-
-            for row in table.select(&tr_selector) {
-                let row_text = extract_text(&row);
-                let tds: Vec<_> = row.select(&td_selector).collect();
-                if tds.is_empty() {
-                    continue;
-                }
-
-                if row_text.contains("Issue Date") {
-                    detail.issue_date = extract_text(tds.last().unwrap());
-                }
-                if row_text.contains("Location") {
-                    detail.location = extract_text(tds.last().unwrap());
-                }
-                if row_text.contains("Beginning Date") {
-                    detail.begin = extract_text(tds.last().unwrap());
-                }
-                if row_text.contains("Ending Date") {
-                    detail.end = extract_text(tds.last().unwrap());
-                }
-                if row_text.contains("Reason") {
-                    detail.reason = extract_text(tds.last().unwrap());
-                }
-                if row_text.contains("Type") {
-                    detail.r#type = extract_text(tds.last().unwrap());
-                }
-                if row_text.contains("Replaced NOTAM") {
-                    detail.replaced = extract_text(tds.last().unwrap());
-                }
+        };
+        let mut parsed = detail::parse_notam_html(&html);
+        parsed.fetched_at = Some(now);
+        if let Some(old) = cached_details.get(&event.notam_id) {
+            parsed.supersedes = old.supersedes.clone();
+            parsed.field_changes = old.field_changes.clone();
+            parsed.superseded_by = old.superseded_by.clone();
+            if fingerprint::details(old) != fingerprint::details(&parsed) {
+                info!("Details of {} changed", event.notam_id);
+                updated.insert(event.notam_id.clone(), lifecycle::diff_events(old, &parsed));
             }
         }
+        cached_details.insert(event.notam_id, parsed);
+    }
 
-        // parse "airspace" table for altitude restrictions
-        if table_text.contains("Airspace Definition") {
-            for row in table.select(&tr_selector) {
-                let row_text = extract_text(&row);
-                let tds: Vec<_> = row.select(&td_selector).collect();
-                if tds.is_empty() {
-                    continue;
-                }
+    // changed TFRs we'd matched are matched again with their new details
+    let mut dropped = HashSet::new();
+    if !updated.is_empty() {
+        let mut matches = store.load_matches()?;
+        matches.retain_mut(|m| {
+            if !updated.contains_key(&m.notam_id) {
+                return true;
+            }
+            let Some(parsed) = cached_details.get_mut(&m.notam_id) else {
+                return true;
+            };
+            if match_event(parsed, criteria) {
+                *m = parsed.clone();
+                true
+            } else {
+                info!("{} no longer matches after its update", m.notam_id);
+                dropped.insert(m.notam_id.clone());
+                false
+            }
+        });
+        store.save_matches(&matches)?;
+    }
 
-                if row_text.contains("Center:") {
-                    detail.airspace.center = extract_text(tds.last().unwrap());
-                }
-                if row_text.contains("Radius:") {
-                    detail.airspace.radius = extract_text(tds.last().unwrap());
-                }
-                if row_text.contains("Altitude:") {
-                    detail.airspace.altitude = extract_text(tds.last().unwrap());
-                }
-                if row_text.contains("Effective Date") {
-                    detail
-                        .airspace
-                        .effective
-                        .push(extract_text(tds.last().unwrap()));
-                }
+    // TFRs we already have details for can start matching later, e.g. once
+    // they come inside the lookahead window
+    for e in &current {
+        if let Some(parsed) = cached_details.get_mut(&e.notam_id)
+            && !matched_ids.contains(&parsed.notam_id)
+            && match_event(parsed, criteria)
+        {
+            info!("Event now matches criteria: {}", e.notam_id);
+            new_matches.push(parsed.clone());
+        }
+    }
+    // the replaced TFR's details are saved again below while it's still
+    // listed, and have to say what replaced it
+    for (old, new_id) in &superseded {
+        for details in [
+            cached_details.get_mut(&old.notam_id),
+            fetched.get_mut(&old.notam_id),
+        ]
+        .into_iter()
+        .flatten()
+        {
+            details.superseded_by = Some(new_id.clone());
+        }
+    }
+    let current: Vec<RawTFREvent> = current
+        .into_iter()
+        .map(|mut e| {
+            e.parsed = fetched
+                .remove(&e.notam_id)
+                .or_else(|| cached_details.get(&e.notam_id).cloned());
+            e
+        })
+        .collect();
+
+    let mut states = store.lifecycle_states()?;
+    let mut changes = Vec::new();
+    if !superseded.is_empty() {
+        let mut matches = store.load_matches()?;
+        for (old, new_id) in &superseded {
+            if let Some(m) = matches.iter_mut().find(|m| m.notam_id == old.notam_id) {
+                m.superseded_by = Some(new_id.clone());
             }
+            // so it isn't also reported as cancelled when it leaves the feed
+            states.insert(old.notam_id.clone(), LifecycleState::Superseded);
+            changes.push(LifecycleChange::superseded(old, new_id, now));
         }
+        store.save_matches(&matches)?;
+    }
 
-        if table_text.contains("Operating Restrictions and Requirements") {
-            detail.restrictions = table_text.clone();
+    let listed: HashSet<_> = current_data.iter().map(|e| e.notam_id.as_str()).collect();
+    let activated = store.activated_ids()?;
+    changes.extend(lifecycle::diff_snapshots(
+        &cached, &current, &listed, &updated, &states, &activated, now,
+    ));
+    for change in changes.iter_mut() {
+        if dropped.contains(&change.notam_id) {
+            change.detail.push_str(", no longer matches");
         }
+    }
+    store.record_lifecycle(&changes)?;
 
-        if table_text.contains("Other Information") {
-            detail.other_info = table_text.clone();
-        }
+    // new matches are announced as such, updates included
+    let announced: HashSet<_> = new_matches.iter().map(|e| e.notam_id.as_str()).collect();
+    let replaced: HashSet<_> = superseded
+        .iter()
+        .filter(|(_, new_id)| announced.contains(new_id.as_str()))
+        .map(|(old, _)| old.notam_id.as_str())
+        .collect();
+    let changes = changes
+        .into_iter()
+        .filter(|c| {
+            c.state != LifecycleState::New
+                && matched_ids.contains(&c.notam_id)
+                && !replaced.contains(c.notam_id.as_str())
+        })
+        .collect();
+
+    store.save_feed(&current)?;
+    store.add_matches(&new_matches)?;
+    info!("Cache updated");
+    Ok(CheckResult {
+        new_matches,
+        changes,
+    })
+}
+
+pub async fn check_feed(store: &mut dyn TfrStore, config: &Config) -> Result<CheckResult> {
+    debug!("Check feed...");
+    match &config.feed_dir {
+        Some(dir) => check_source(&ReplaySource::new(dir), store, config).await,
+        None => check_source(&HttpSource::new(&config.faa_base_url), store, config).await,
     }
+}
 
-    detail
+async fn check_source(
+    source: &impl FeedSource,
+    store: &mut dyn TfrStore,
+    config: &Config,
+) -> Result<CheckResult> {
+    let criteria = config.criteria();
+    let refresh_hours = config.detail_refresh_hours;
+    match &config.capture_dir {
+        Some(archive) => {
+            let now = Utc::now();
+            if let Err(e) = archive::prune(archive, config.capture_keep_days, now) {
+                warn!("Failed to delete old captures: {e:#}");
+            }
+            let recorder = Recorder::new(source, archive, now)?;
+            process_feed(&recorder, store, &criteria, refresh_hours).await
+        }
+        None => process_feed(source, store, &criteria, refresh_hours).await,
+    }
 }
 
 /// Summarize matched events (today_matches_count, unique_cities_count).
@@ -361,59 +815,191 @@ pub fn summarize_matched_events(events: &[ParsedTFREvent]) -> (usize, usize) {
     (today_count, cities.len())
 }
 
-use chrono::{DateTime, NaiveDateTime, Utc};
-
-pub fn load_matched_cache_sorted() -> Vec<ParsedTFREvent> {
-    let mut events = load_matched_cache();
-    /// TODO
-    // events.sort_by(|a, b| {
-    //     let a_date = a.as_ref().and_then(|p| parse_date(&p.issue_date));
-    //     let b_date = b.as_ref().and_then(|p| parse_date(&p.issue_date));
-    //     b_date.cmp(&a_date)
-    // });
-    events
-}
-
-fn parse_date(date_str: &str) -> Option<DateTime<Utc>> {
-    // FAA format looks like "03/16/2025 13:00 UTC"
-    if let Ok(ndt) = NaiveDateTime::parse_from_str(date_str, "%m/%d/%Y %H:%M %Z") {
-        Some(DateTime::<Utc>::from_naive_utc_and_offset(ndt, Utc))
-    } else {
-        None
-    }
-}
-
-pub async fn refresh_tfr_results() -> Result<crate::FeedResult> {
+pub async fn refresh_tfr_results(
+    store: &mut dyn TfrStore,
+    config: &Config,
+) -> Result<crate::FeedResult> {
     use log::info;
 
-    // Load previously seen events
-    let mut seen_matches = load_matched_cache();
-    let prev_count = seen_matches.len();
-
-    // Fetch fresh data from FAA
-    let new_matches = check_feed().await.unwrap_or_default();
-
-    let mut unseen = Vec::new();
-    for e in new_matches.iter().rev() {
-        if !seen_matches.iter().any(|m| m.notam_id == e.notam_id) {
-            unseen.push(e.clone());
-            seen_matches.insert(0, e.clone()); // prepend new ones
-        }
-    }
+    // Fetch fresh data from FAA; on failure still show what we have
+    let mut warnings = Vec::new();
+    let checked = check_feed(store, config).await.unwrap_or_else(|e| {
+        error!("Failed to check feed: {e:#}");
+        warnings.push(format!("Failed to check feed: {e:#}"));
+        CheckResult::default()
+    });
 
-    save_matched_cache(&seen_matches)?;
+    // new matches are already on top of the cache
+    let seen_matches = store.load_matches()?;
     info!(
         "Cache updated: {} total, {} new",
         seen_matches.len(),
-        unseen.len()
+        checked.new_matches.len()
     );
 
-    let (today_total, city_count) = summarize_matched_events(&seen_matches);
+    warnings.extend(store.take_warnings());
+    let mut result = feed_result(store, seen_matches, checked.new_matches.len(), warnings)?;
+    result.changes = checked.changes;
+    Ok(result)
+}
+
+/// Re-run the current filters over everything already downloaded, without
+/// touching the network. Used after settings change.
+pub fn refilter_cached(store: &mut dyn TfrStore, config: &Config) -> Result<crate::FeedResult> {
+    let criteria = config.criteria();
+
+    // everything we have details for: previous matches, and the raw feed.
+    // The feed entry wins over the type scraped from the detail page.
+    let raw = store.load_feed()?;
+    let feed: HashMap<_, _> = raw.iter().map(|e| (e.notam_id.as_str(), e)).collect();
+    let previous = store.load_matches()?;
+    let mut candidates: HashMap<String, ParsedTFREvent> = previous
+        .iter()
+        .filter(|e| match feed.get(e.notam_id.as_str()) {
+            Some(entry) => criteria.passes_feed(entry),
+            None => e.r#type.is_empty() || criteria.types.matches(&e.r#type),
+        })
+        .map(|e| (e.notam_id.clone(), e.clone()))
+        .collect();
+    for parsed in get_filtered_events(&raw, &criteria)
+        .into_iter()
+        .filter_map(|e| e.parsed)
+    {
+        candidates.insert(parsed.notam_id.clone(), parsed);
+    }
+    candidates.retain(|_, e| match_event(e, &criteria));
+
+    // previous matches keep their place, newly matching ones go on top in
+    // NOTAM order
+    let mut kept = Vec::new();
+    for e in &previous {
+        if let Some(event) = candidates.remove(&e.notam_id) {
+            kept.push(event);
+        }
+    }
+    let mut events: Vec<_> = candidates.into_values().collect();
+    events.sort_by(|a, b| a.notam_id.cmp(&b.notam_id));
+    events.extend(kept);
+
+    store.save_matches(&events)?;
+    info!("Re-filtered cache: {} matching", events.len());
+    let warnings = store.take_warnings();
+    feed_result(store, events, 0, warnings)
+}
+
+fn feed_result(
+    store: &dyn TfrStore,
+    events: Vec<ParsedTFREvent>,
+    unseen_count: usize,
+    warnings: Vec<String>,
+) -> Result<crate::FeedResult> {
+    let (today_total, city_count) = summarize_matched_events(&events);
 
     Ok(crate::FeedResult {
-        events: seen_matches,
-        unseen_count: unseen.len(),
+        events,
+        unseen_count,
         today_count: today_total,
         city_today_count: city_count,
+        warnings,
+        states: store.lifecycle_states()?,
+        changes: Vec::new(),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(include: &[&str], exclude: &[&str]) -> TypeFilter {
+        TypeFilter {
+            include: include.iter().map(|t| t.to_string()).collect(),
+            exclude: exclude.iter().map(|t| t.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn type_filter_ignores_case() {
+        let security = filter(&["SECURITY"], &[]);
+        assert!(security.matches("SECURITY"));
+        assert!(security.matches(" security "));
+        assert!(!security.matches("VIP"));
+        assert!(!security.matches(""));
+    }
+
+    #[test]
+    fn type_filter_exclude_only() {
+        let no_hazards = filter(&[], &["Hazards"]);
+        assert!(no_hazards.matches("SECURITY"));
+        assert!(no_hazards.matches(""));
+        assert!(!no_hazards.matches("HAZARDS"));
+        assert_eq!(no_hazards.describe(), "all except Hazards");
+    }
+
+    #[test]
+    fn type_filter_include_and_exclude() {
+        let types = filter(&["SECURITY", "VIP"], &["VIP"]);
+        assert!(types.matches("security"));
+        assert!(!types.matches("VIP"));
+        assert!(!types.matches("HAZARDS"));
+        assert_eq!(types.describe(), "SECURITY, VIP except VIP");
+        assert_eq!(
+            filter(&["SECURITY", "VIP"], &[]).describe(),
+            "SECURITY, VIP"
+        );
+    }
+
+    #[test]
+    fn empty_type_filter_passes_everything() {
+        let all = TypeFilter::default();
+        assert!(all.is_empty());
+        assert!(all.matches("SECURITY"));
+        assert!(all.matches(""));
+        assert_eq!(all.describe(), "all");
+    }
+
+    fn feed_event(state: Option<&str>, facility: Option<&str>) -> RawTFREvent {
+        RawTFREvent {
+            notam_id: "5/1234".to_string(),
+            r#type: Some("SECURITY".to_string()),
+            state: state.map(str::to_string),
+            facility: facility.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn state_and_facility_filters() {
+        let criteria = MatchCriteria {
+            states: filter(&["MD", "DC"], &[]),
+            facilities: filter(&[], &["ZNY"]),
+            ..Default::default()
+        };
+        assert!(criteria.passes_feed(&feed_event(Some("md"), Some("ZDC"))));
+        assert!(!criteria.passes_feed(&feed_event(Some("VA"), Some("ZDC"))));
+        assert!(!criteria.passes_feed(&feed_event(Some("DC"), Some("zny"))));
+
+        let no_type = RawTFREvent {
+            r#type: None,
+            ..feed_event(Some("MD"), None)
+        };
+        assert!(criteria.passes_feed(&no_type));
+        let vip_only = MatchCriteria {
+            types: filter(&["VIP"], &[]),
+            ..criteria
+        };
+        assert!(!vip_only.passes_feed(&feed_event(Some("MD"), Some("ZDC"))));
+    }
+
+    #[test]
+    fn missing_state_or_facility_passes() {
+        let criteria = MatchCriteria {
+            states: filter(&["MD"], &[]),
+            facilities: filter(&["ZDC"], &[]),
+            ..Default::default()
+        };
+        assert!(criteria.passes_feed(&feed_event(None, Some("ZDC"))));
+        assert!(criteria.passes_feed(&feed_event(Some("MD"), None)));
+        assert!(criteria.passes_feed(&feed_event(None, None)));
+        assert!(!criteria.passes_feed(&feed_event(None, Some("ZNY"))));
+    }
+}
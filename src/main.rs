@@ -7,18 +7,72 @@
 // You should have received a copy of the GNU General Public License along with TFRAlert. If not, see <https://www.gnu.org/licenses/>.
 
 use async_std::task::sleep;
+use clap::Parser;
+use cli::{Cli, Command};
+use config::Config;
 use dioxus::prelude::*;
 
+mod altitude;
+mod archive;
+mod cache_file;
+mod cli;
+mod config;
+mod daemon;
+mod detail;
+mod fingerprint;
+mod geo;
+mod keywords;
+mod lifecycle;
 mod logic;
+mod migrate;
+mod notam_text;
 mod notify;
+mod paths;
+mod rules;
+mod schedule;
+mod settings;
+mod source;
+mod store;
 
 fn main() {
-    dioxus::launch(app);
+    let cli = Cli::parse();
+
+    // logs go to stderr; commands other than the daemon keep them quiet
+    if let Some(command) = &cli.command {
+        let level = if matches!(command, Command::Daemon) {
+            "info"
+        } else {
+            "warn"
+        };
+        env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(level)).init();
+    }
+
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{e:#}");
+            std::process::exit(cli::EXIT_ERROR);
+        }
+    };
+    if let Err(e) = paths::init_data_dir(cli.data_dir, store::JSON_FILES) {
+        eprintln!("{e:#}");
+        std::process::exit(cli::EXIT_ERROR);
+    }
+
+    let Some(command) = cli.command else {
+        LaunchBuilder::new().with_context(config).launch(app);
+        return;
+    };
+
+    std::process::exit(cli::exit_code(cli::run(command, cli.json, config)));
 }
 
 const NOTAM_DETAIL_URL_PRETTY: &str = "https://tfr.faa.gov/tfr3/?page=detail_";
-const MATCHES: &str = "tfr_matches.json";
-const REFRESH_SECONDS: u64 = 600; // configurable later
+
+/// Human-readable page for a NOTAM on tfr.faa.gov
+pub fn detail_url(notam_id: &str) -> String {
+    format!("{}{}", NOTAM_DETAIL_URL_PRETTY, notam_id.replace("/", "_"))
+}
 
 #[derive(Debug, Clone)]
 enum LoadState {
@@ -33,51 +87,111 @@ pub struct FeedResult {
     unseen_count: usize,
     today_count: usize,
     city_today_count: usize,
+    /// Shown above the list, e.g. a cache that had to be restored
+    warnings: Vec<String>,
+    /// Latest lifecycle state of each TFR, for the badges
+    states: std::collections::HashMap<String, lifecycle::LifecycleState>,
+    /// What happened to earlier matches during this refresh
+    changes: Vec<lifecycle::LifecycleChange>,
+}
+
+impl FeedResult {
+    /// Events not seen before this refresh, oldest first
+    pub fn new_events(&self) -> Vec<logic::ParsedTFREvent> {
+        self.events
+            .iter()
+            .take(self.unseen_count)
+            .rev()
+            .cloned()
+            .collect()
+    }
 }
 
 #[component]
 pub fn app() -> Element {
+    let initial_config = use_context::<Config>();
+    let mut config = use_signal(|| initial_config);
     // refresh_counter is incremented to trigger new fetches
     let mut refresh_counter = use_signal(|| 0u64);
     let mut feed_state = use_signal(|| LoadState::Loading);
+    let mut show_settings = use_signal(|| false);
+    // criteria() reads the keyword file, so only when the config changes
+    let criteria_summary = use_memo(move || config.read().criteria().describe());
 
-    use_future({
-        let mut feed_state = feed_state.clone();
-        let refresh_counter = refresh_counter();
-        move || async move {
+    // re-runs whenever refresh_counter changes
+    use_effect(move || {
+        refresh_counter();
+        let config = config.peek().clone();
+        spawn(async move {
             feed_state.set(LoadState::Loading);
-            match logic::refresh_tfr_results().await {
-                Ok(result) => feed_state.set(LoadState::Loaded(result)),
+            let mut store = match store::open(config.storage) {
+                Ok(store) => store,
+                Err(e) => {
+                    feed_state.set(LoadState::Error(format!("{e:#}")));
+                    return;
+                }
+            };
+            match logic::refresh_tfr_results(store.as_mut(), &config).await {
+                Ok(result) => {
+                    let new_events = result.new_events();
+                    if !new_events.is_empty() && config.notifications.enabled {
+                        let ids: Vec<_> = new_events.iter().map(|e| e.notam_id.clone()).collect();
+                        if let Err(e) = store.record_notifications(&ids) {
+                            log::error!("Failed to record notifications: {e:#}");
+                        }
+                        let prefs = config.notifications.clone();
+                        std::thread::spawn(move || notify::notify(&new_events, &prefs));
+                    }
+                    if !result.changes.is_empty() {
+                        let changes = result.changes.clone();
+                        let prefs = config.notifications.clone();
+                        std::thread::spawn(move || notify::notify_changes(&changes, &prefs));
+                    }
+                    feed_state.set(LoadState::Loaded(result));
+                }
                 Err(e) => feed_state.set(LoadState::Error(e.to_string())),
             }
-        }
+        });
     });
 
-    use_future({
-        let mut refresh_counter = refresh_counter.clone();
-        move || async move {
-            loop {
-                sleep(std::time::Duration::from_secs(REFRESH_SECONDS)).await;
-                refresh_counter += 1;
-            }
+    use_future(move || async move {
+        loop {
+            let refresh_seconds = config.peek().refresh_seconds;
+            sleep(std::time::Duration::from_secs(refresh_seconds)).await;
+            refresh_counter += 1;
         }
     });
 
+    // new settings apply to what we already have straight away
+    let on_save = move |updated: Config| {
+        let refiltered = store::open(updated.storage)
+            .and_then(|mut store| logic::refilter_cached(store.as_mut(), &updated));
+        match refiltered {
+            Ok(result) => feed_state.set(LoadState::Loaded(result)),
+            Err(e) => feed_state.set(LoadState::Error(e.to_string())),
+        }
+        config.set(updated);
+        show_settings.set(false);
+    };
+
+    if show_settings() {
+        return rsx! {
+            document::Stylesheet { href: asset!("/assets/style.css") }
+            div { class: "app-container",
+                h2 { "TFRAlert" }
+                settings::Settings {
+                    config: config(),
+                    on_save,
+                    on_cancel: move |_| show_settings.set(false),
+                }
+            }
+        };
+    }
+
     let content = match feed_state() {
         LoadState::Loading => rsx!(p { "Loading feed..." }),
         LoadState::Error(e) => rsx!(p { "Error loading feed: {e}" }),
         LoadState::Loaded(result) => {
-            if result.unseen_count > 0 {
-                let new_events = result
-                    .events
-                    .iter()
-                    .take(result.unseen_count)
-                    .rev() // chronological order for notifications
-                    .cloned()
-                    .collect::<Vec<_>>();
-                std::thread::spawn(move || notify::notify(&new_events));
-            }
-
             let event_items = result
                 .events
                 .iter()
@@ -85,11 +199,20 @@ pub fn app() -> Element {
                     let notam_id = &event.notam_id;
                     let city = &event.location;
                     let date = &event.issue_date;
-                    let url = format!(
-                        "{}{}",
-                        NOTAM_DETAIL_URL_PRETTY,
-                        &event.notam_id.replace("/", "_")
-                    );
+                    let reasons = event.match_reasons.join(", ");
+                    let incomplete = event.parse_quality.filter(|q| *q < 100);
+                    let parse_problems = event
+                        .parse_warnings
+                        .iter()
+                        .map(|w| w.to_string())
+                        .collect::<Vec<_>>()
+                        .join("\n");
+                    let url = detail_url(&event.notam_id);
+                    // "new" is every match's first state, not worth a badge
+                    let state = result
+                        .states
+                        .get(notam_id)
+                        .filter(|s| **s != lifecycle::LifecycleState::New);
 
                     rsx! {
                         li { class: "event-item",
@@ -101,15 +224,41 @@ pub fn app() -> Element {
                                     "{notam_id}"
                                 }
                                 span { "{date} {city}" }
+                                if let Some(state) = state {
+                                    span { class: "lifecycle-state state-{state}", " {state}" }
+                                }
+                                if let Some(err) = event.airspace.geometry_error() {
+                                    span { class: "parse-warning", title: "{err}", " ⚠ location not parsed" }
+                                }
+                                if let Some(err) = event.airspace.altitude_error() {
+                                    span { class: "parse-warning", title: "{err}", " ⚠ altitude not parsed" }
+                                }
+                                if let Some(quality) = incomplete {
+                                    span { class: "parse-warning", title: "{parse_problems}", " ⚠ details {quality}% parsed" }
+                                }
+                                if !reasons.is_empty() {
+                                    div { class: "match-reasons", "{reasons}" }
+                                }
+                                if let Some(previous) = event.supersedes.last() {
+                                    div { class: "supersedes", "Update to {previous}" }
+                                    for change in event.field_changes.iter() {
+                                        div { class: "field-change", "{change}" }
+                                    }
+                                }
+                                if let Some(newer) = &event.superseded_by {
+                                    div { class: "superseded-by", "Replaced by {newer}" }
+                                }
                             }
                         }
                     }
                 })
                 .collect::<Vec<_>>();
 
+            let matches_path = store::location(config.read().storage).display().to_string();
             let summary = format!(
-                "Showing {} items (type: Security, altitude: 0–400 ft AGL)",
-                event_items.len()
+                "Showing {} items ({})",
+                event_items.len(),
+                criteria_summary()
             );
 
             rsx! {
@@ -119,17 +268,36 @@ pub fn app() -> Element {
                     div { class: "header-row",
                         p { class: "summary", "{summary}" }
 
-                        button {
-                            class: "refresh-button",
-                            onclick: move |_| refresh_counter += 1,
-                            "Refresh"
+                        div { class: "header-buttons",
+                            button {
+                                class: "secondary-button",
+                                onclick: move |_| show_settings.set(true),
+                                "Settings"
+                            }
+                            button {
+                                class: "refresh-button",
+                                onclick: move |_| refresh_counter += 1,
+                                "Refresh"
+                            }
+                        }
+                    }
+
+                    for warning in result.warnings.iter() {
+                        p { class: "store-warning", "⚠ {warning}" }
+                    }
+                    for change in result.changes.iter() {
+                        div { class: "lifecycle-change",
+                            "{change.describe()}"
+                            for field in change.changes.iter() {
+                                div { class: "field-change", "{field}" }
+                            }
                         }
                     }
 
                     ul { class: "event-list", {event_items.into_iter()} }
 
                     p { style: "margin-top: 1em; font-style: italic;",
-                        "For details of all events see {MATCHES}"
+                        "For details of all events see {matches_path}"
                     }
                 }
             }
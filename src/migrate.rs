@@ -0,0 +1,160 @@
+// Copyright 2025. This file is part of TFRAlert.
+
+// TFRAlert is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+// TFRAlert is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License along with TFRAlert. If not, see <https://www.gnu.org/licenses/>.
+
+//! Upgrades cached events written by older versions, one version at a time,
+//! before they're deserialized. Each step works on plain JSON so it keeps
+//! working however the structs change later.
+//!
+//! When `RawTFREvent` or `ParsedTFREvent` changes in a way old data can't
+//! deserialize into (a renamed or restructured field, a new field without a
+//! default), bump `DATA_VERSION`, add a step to `MIGRATIONS`, and a fixture
+//! written by the old version under `tests/fixtures/cache`.
+//!
+//! Versions:
+//! - 0: bare JSON array. Early releases had one flat `airspace` with
+//!   `center`/`radius`/`altitude`; later ones already have `areas`.
+//! - 1: `{"version": 1, "data": [...]}`, airspace as a list of `areas`.
+
+use anyhow::{Result, bail};
+use serde_json::{Map, Value, json};
+
+/// Version of the events we write
+pub const DATA_VERSION: u64 = 1;
+
+type Migration = fn(&mut Value);
+
+/// `MIGRATIONS[n]` upgrades one cached item from version `n` to `n + 1`
+const MIGRATIONS: &[Migration] = &[airspace_areas];
+
+const _: () = assert!(MIGRATIONS.len() as u64 == DATA_VERSION);
+
+/// Bring one cached item (a feed entry, parsed event or notification
+/// record) from version `from` up to `DATA_VERSION`
+pub fn upgrade(item: &mut Value, from: u64) -> Result<()> {
+    let Some(steps) = MIGRATIONS.get(from as usize..) else {
+        bail!("written by a newer TFRAlert (version {from})");
+    };
+    for step in steps {
+        step(item);
+    }
+    Ok(())
+}
+
+/// The parsed details of a feed entry or matched event, wherever they are
+fn parsed_events(item: &mut Value) -> Vec<&mut Map<String, Value>> {
+    let Some(object) = item.as_object_mut() else {
+        return Vec::new();
+    };
+    if object.contains_key("airspace") {
+        return vec![object];
+    }
+    match object.get_mut("parsed").and_then(Value::as_object_mut) {
+        Some(parsed) => vec![parsed],
+        None => Vec::new(),
+    }
+}
+
+/// 0 → 1: a single flat area becomes the first entry of `areas`. The old
+/// `circle`/`geometry_error` are dropped; they're worked out again from the
+/// text when the event is next matched.
+fn airspace_areas(item: &mut Value) {
+    for event in parsed_events(item) {
+        let Some(airspace) = event.get_mut("airspace").and_then(Value::as_object_mut) else {
+            continue;
+        };
+        if airspace.contains_key("areas") {
+            continue;
+        }
+        let mut text = |key: &str| airspace.remove(key).unwrap_or(json!(""));
+        let (center, radius, altitude) = (text("center"), text("radius"), text("altitude"));
+        airspace.remove("circle");
+        airspace.remove("geometry_error");
+
+        let blank = [&center, &radius, &altitude]
+            .iter()
+            .all(|v| v.as_str().is_none_or(str::is_empty));
+        let areas = if blank {
+            Vec::new()
+        } else {
+            vec![json!({ "center": center, "radius": radius, "altitude": altitude })]
+        };
+        airspace.insert("areas".to_string(), Value::Array(areas));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cache_file;
+    use crate::logic::{ParsedTFREvent, RawTFREvent};
+
+    fn matches(fixture: &str) -> Vec<ParsedTFREvent> {
+        let mut events: Vec<ParsedTFREvent> = cache_file::parse_str(fixture).unwrap();
+        for event in &mut events {
+            event.resolve_missing();
+        }
+        events
+    }
+
+    #[test]
+    fn v0_flat_airspace() {
+        let events = matches(include_str!("../tests/fixtures/cache/v0_flat_matches.json"));
+        assert_eq!(events.len(), 2);
+
+        let area = &events[0].airspace.areas[0];
+        assert_eq!(events[0].airspace.areas.len(), 1);
+        assert_eq!(area.center, "385837N0762910W");
+        assert_eq!(area.radius, "3 nautical miles");
+        assert!(area.shape.is_some());
+        assert!(area.altitude_band.is_some());
+        assert!(!events[0].active_windows.is_empty());
+
+        // no location at all stays that way rather than becoming a blank area
+        assert!(events[1].airspace.areas.is_empty());
+    }
+
+    #[test]
+    fn v0_circle_airspace() {
+        let feed: Vec<RawTFREvent> =
+            cache_file::parse_str(include_str!("../tests/fixtures/cache/v0_circle_feed.json"))
+                .unwrap();
+        assert_eq!(feed.len(), 2);
+        assert!(feed[1].parsed.is_none());
+
+        let parsed = feed[0].parsed.as_ref().unwrap();
+        assert_eq!(parsed.airspace.areas.len(), 1);
+        assert_eq!(parsed.airspace.areas[0].radius, "30NM");
+    }
+
+    #[test]
+    fn v0_areas() {
+        let events = matches(include_str!(
+            "../tests/fixtures/cache/v0_areas_matches.json"
+        ));
+        assert_eq!(events[0].airspace.areas.len(), 2);
+        assert!(!events[0].airspace.areas[1].boundary.is_empty());
+        assert!(events[0].airspace.areas.iter().all(|a| a.shape.is_some()));
+    }
+
+    #[test]
+    fn v1() {
+        let events = matches(include_str!("../tests/fixtures/cache/v1_matches.json"));
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].airspace.areas.len(), 1);
+        assert_eq!(events[0].match_reasons, ["keyword 'stadium'"]);
+    }
+
+    #[test]
+    fn newer_version_is_refused() {
+        let result: Result<Vec<ParsedTFREvent>, _> =
+            cache_file::parse_str(r#"{"version": 999, "data": []}"#);
+        assert!(matches!(
+            result,
+            Err(cache_file::CacheError::Unsupported(_))
+        ));
+    }
+}
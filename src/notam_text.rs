@@ -0,0 +1,215 @@
+// Copyright 2025. This file is part of TFRAlert.
+
+// TFRAlert is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+// TFRAlert is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License along with TFRAlert. If not, see <https://www.gnu.org/licenses/>.
+
+//! The NOTAM as published, which detail pages carry alongside their tables:
+//! domestic ("!FDC 5/4870 ZDC MD..AIRSPACE ... 3NM RADIUS OF
+//! 385837N0762910W SFC-2999FT MSL 2510051400-2510052300") or ICAO, with
+//! `Q)`, `B)`/`C)` and `F)`/`G)` items. It says the same as the tables, so
+//! it can fill in what they're missing and show where they disagree.
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use regex::Regex;
+use std::sync::LazyLock;
+
+static DOMESTIC_ID: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"!FDC\s+(\d{1,2}/\d{1,4})\s+([A-Z]{3})\b").unwrap());
+static Q_LINE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"Q\)\s*([A-Z]{4})/(Q[A-Z]{4})/[A-Z]*/[A-Z]*/[A-Z]*/(\d{3})/(\d{3})/(\d{4}[NS]\d{5}[EW])(\d{3})",
+    )
+    .unwrap()
+});
+static CIRCLE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(\d+(?:\.\d+)?)\s*NM\s+RADIUS\s+OF\s+(\d{4,6}(?:\.\d+)?[NS]\d{5,7}(?:\.\d+)?[EW])")
+        .unwrap()
+});
+const LIMIT: &str = r"SFC|UNL|FL\s?\d{3}|\d+\s?FT(?:\s+(?:AGL|MSL))?";
+static ALTITUDE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(&format!(r"\b((?:{LIMIT})-(?:{LIMIT}))")).unwrap());
+static LOWER_UPPER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(&format!(r"F\)\s*({LIMIT})\s+G\)\s*({LIMIT})")).unwrap());
+static ICAO_TIMES: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"B\)\s*(\d{10})\s+C\)\s*(\d{10}|PERM)").unwrap());
+static DOMESTIC_TIMES: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\b(\d{10})-(\d{10}|PERM|UFN)\b").unwrap());
+
+/// `Q)` item: FIR/code/traffic/purpose/scope/lower/upper/area
+#[derive(Debug, Clone, PartialEq)]
+pub struct QLine {
+    pub fir: String,
+    /// e.g. "QRTCA", restricted area activated
+    pub code: String,
+    /// Hundreds of feet; 999 for unlimited
+    pub lower: u32,
+    pub upper: u32,
+    /// Degrees and minutes, e.g. "3858N07629W"
+    pub center: String,
+    pub radius_nm: u32,
+}
+
+impl QLine {
+    /// e.g. "SFC-FL029", in a form `altitude::parse_altitude` reads
+    fn altitude(&self) -> String {
+        let limit = |hundreds: u32| match hundreds {
+            0 => "SFC".to_string(),
+            999 => "UNL".to_string(),
+            _ => format!("FL{hundreds:03}"),
+        };
+        format!("{}-{}", limit(self.lower), limit(self.upper))
+    }
+}
+
+/// What the NOTAM text says, as published. Anything not found is `None`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NotamText {
+    /// e.g. "5/4870"
+    pub notam_id: Option<String>,
+    /// Issuing ARTCC, e.g. "ZDC"
+    pub facility: Option<String>,
+    pub q_line: Option<QLine>,
+    /// e.g. "385837N0762910W"
+    pub center: Option<String>,
+    /// e.g. "3NM"
+    pub radius: Option<String>,
+    /// e.g. "SFC-2999FT MSL"
+    pub altitude: Option<String>,
+    pub start: Option<DateTime<Utc>>,
+    /// `None` with a `start` means permanent
+    pub end: Option<DateTime<Utc>>,
+}
+
+/// "2510051400" as UTC
+fn timestamp(text: &str) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(text, "%y%m%d%H%M")
+        .ok()
+        .map(|t| t.and_utc())
+}
+
+impl NotamText {
+    pub fn parse(text: &str) -> NotamText {
+        // the FAA wraps lines anywhere, even inside an item
+        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        let mut parsed = NotamText::default();
+
+        if let Some(caps) = DOMESTIC_ID.captures(&text) {
+            parsed.notam_id = Some(caps[1].to_string());
+            parsed.facility = Some(caps[2].to_string());
+        }
+        if let Some(caps) = Q_LINE.captures(&text) {
+            parsed.q_line = Some(QLine {
+                fir: caps[1].to_string(),
+                code: caps[2].to_string(),
+                lower: caps[3].parse().unwrap_or(0),
+                upper: caps[4].parse().unwrap_or(999),
+                center: caps[5].to_string(),
+                radius_nm: caps[6].parse().unwrap_or(0),
+            });
+        }
+        if let Some(caps) = CIRCLE.captures(&text) {
+            parsed.radius = Some(format!("{}NM", &caps[1]));
+            parsed.center = Some(caps[2].to_string());
+        }
+        parsed.altitude = match LOWER_UPPER.captures(&text) {
+            Some(caps) => Some(format!("{}-{}", &caps[1], &caps[2])),
+            None => ALTITUDE.captures(&text).map(|caps| caps[1].to_string()),
+        };
+
+        let times = ICAO_TIMES
+            .captures(&text)
+            .or_else(|| DOMESTIC_TIMES.captures_iter(&text).last());
+        if let Some(caps) = times
+            && let Some(start) = timestamp(&caps[1])
+        {
+            parsed.start = Some(start);
+            parsed.end = timestamp(&caps[2]);
+        }
+        parsed
+    }
+
+    /// The circle's center, or the `Q)` item's rougher one
+    pub fn center(&self) -> Option<String> {
+        self.center
+            .clone()
+            .or_else(|| self.q_line.as_ref().map(|q| q.center.clone()))
+    }
+
+    pub fn radius(&self) -> Option<String> {
+        self.radius
+            .clone()
+            .or_else(|| self.q_line.as_ref().map(|q| format!("{}NM", q.radius_nm)))
+    }
+
+    /// Lower and upper limits, from `F)`/`G)` or the text, else the `Q)`
+    /// item's flight levels
+    pub fn altitude(&self) -> Option<String> {
+        self.altitude
+            .clone()
+            .or_else(|| self.q_line.as_ref().map(QLine::altitude))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::altitude::{AltitudeReference, parse_altitude};
+
+    #[test]
+    fn domestic() {
+        let text = NotamText::parse(
+            "!FDC 5/4870 ZDC MD..AIRSPACE ANNAPOLIS, MD..TEMPORARY FLIGHT\n\
+             RESTRICTIONS WI AN AREA DEFINED AS 3NM RADIUS OF 385837N0762910W\n\
+             SFC-2999FT MSL 2510051400-2510052300",
+        );
+        assert_eq!(text.notam_id.as_deref(), Some("5/4870"));
+        assert_eq!(text.facility.as_deref(), Some("ZDC"));
+        assert_eq!(text.center.as_deref(), Some("385837N0762910W"));
+        assert_eq!(text.radius.as_deref(), Some("3NM"));
+        assert_eq!(text.altitude.as_deref(), Some("SFC-2999FT MSL"));
+        assert_eq!(text.start, timestamp("2510051400"));
+        assert_eq!(text.end, timestamp("2510052300"));
+        assert!(text.q_line.is_none());
+    }
+
+    #[test]
+    fn icao() {
+        let text = NotamText::parse(
+            "A2121/25 NOTAMN\n\
+             Q) KZJX/QRTCA/IV/BO/W/000/999/2820N08023W013\n\
+             A) KZJX B) 2510142200 C) PERM\n\
+             E) TEMPORARY FLIGHT RESTRICTIONS FOR SPACE OPERATIONS\n\
+             F) SFC G) UNL",
+        );
+        let q = text.q_line.as_ref().unwrap();
+        assert_eq!((q.fir.as_str(), q.code.as_str()), ("KZJX", "QRTCA"));
+        assert_eq!((q.lower, q.upper, q.radius_nm), (0, 999, 13));
+        assert_eq!(text.center().as_deref(), Some("2820N08023W"));
+        assert_eq!(text.radius().as_deref(), Some("13NM"));
+        assert_eq!(text.altitude().as_deref(), Some("SFC-UNL"));
+        assert_eq!(text.start, timestamp("2510142200"));
+        assert_eq!(text.end, None);
+    }
+
+    #[test]
+    fn q_line_levels_read_as_altitude() {
+        let text = NotamText::parse("Q) KZDC/QRTCA/IV/BO/W/000/029/3858N07629W003");
+        let band = parse_altitude(&text.altitude().unwrap()).unwrap();
+        assert_eq!(band.floor.feet, 0);
+        let ceiling = band.ceiling.unwrap();
+        assert_eq!(
+            (ceiling.feet, ceiling.reference),
+            (2900, AltitudeReference::Fl)
+        );
+    }
+
+    #[test]
+    fn nothing_to_find() {
+        assert_eq!(NotamText::parse(""), NotamText::default());
+        let other = NotamText::parse("Authority: Title 49 CFR section 1.5");
+        assert_eq!(other, NotamText::default());
+    }
+}
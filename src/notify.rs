@@ -6,6 +6,8 @@
 
 // You should have received a copy of the GNU General Public License along with TFRAlert. If not, see <https://www.gnu.org/licenses/>.
 
+use crate::config::NotificationConfig;
+use crate::lifecycle::LifecycleChange;
 use crate::logic::ParsedTFREvent;
 use notify_rust::Notification;
 
@@ -15,29 +17,76 @@ pub struct NotificationText {
     body: String,
 }
 
-fn get_notification_text(events: &[ParsedTFREvent]) -> Option<NotificationText> {
-    match events.len() {
-        0 => None,
-        1 => build_single_notification(&events[0]),
-        _ => build_batch_notification(events),
+/// One notification per event, or a single summary if there are more
+/// than `summarize_after`
+fn get_notification_text(
+    events: &[ParsedTFREvent],
+    summarize_after: usize,
+) -> Vec<NotificationText> {
+    if events.len() > summarize_after {
+        build_batch_notification(events).into_iter().collect()
+    } else {
+        events
+            .iter()
+            .filter_map(build_single_notification)
+            .collect()
     }
 }
 
-pub fn notify(events: &[ParsedTFREvent]) {
-    if let Some(notif) = get_notification_text(events) {
-        show_notification(&notif.title, &notif.body);
-    } else {
+pub fn notify(events: &[ParsedTFREvent], prefs: &NotificationConfig) {
+    if !prefs.enabled {
+        log::debug!("Notifications disabled");
+        return;
+    }
+    let notifs = get_notification_text(events, prefs.summarize_after);
+    if notifs.is_empty() {
         log::debug!("No new TFRs");
     }
+    for notif in notifs {
+        show_notification(&notif.title, &notif.body);
+    }
+}
+
+/// "TFR 5/1234 cancelled" and the like for TFRs we already alerted on
+pub fn notify_changes(changes: &[LifecycleChange], prefs: &NotificationConfig) {
+    if !prefs.enabled || changes.is_empty() {
+        return;
+    }
+    if changes.len() > prefs.summarize_after {
+        let body = changes
+            .iter()
+            .map(|c| format!("{} {}", c.notam_id, c.state))
+            .collect::<Vec<_>>()
+            .join(", ");
+        show_notification(&format!("{} TFR updates", changes.len()), &body);
+        return;
+    }
+    for change in changes {
+        let mut title = format!("TFR {} {}", change.notam_id, change.state);
+        if !change.location.is_empty() {
+            title.push_str(&format!(" ({})", change.location));
+        }
+        let mut body = change.detail.clone();
+        for field in &change.changes {
+            body.push_str(&format!("\n{field}"));
+        }
+        show_notification(&title, &body);
+    }
 }
 
 fn build_single_notification(event: &ParsedTFREvent) -> Option<NotificationText> {
-    let mut title = format!("New TFR: {}", event.notam_id);
+    let mut title = match event.supersedes.last() {
+        Some(previous) => format!("Updated TFR: {} (replaces {previous})", event.notam_id),
+        None => format!("New TFR: {}", event.notam_id),
+    };
     if !event.location.is_empty() {
         title.push_str(&format!(" ({})", event.location));
     }
 
     let mut body = String::new();
+    for change in &event.field_changes {
+        body.push_str(&format!("{change}\n"));
+    }
     if !event.reason.is_empty() {
         body.push_str(&format!("Reason: {}\n", event.reason));
     }
@@ -47,6 +96,9 @@ fn build_single_notification(event: &ParsedTFREvent) -> Option<NotificationText>
     if !event.begin.is_empty() && !event.end.is_empty() {
         body.push_str(&format!("{} - {}\n", event.begin, event.end));
     }
+    if !event.match_reasons.is_empty() {
+        body.push_str(&format!("Matched: {}\n", event.match_reasons.join(", ")));
+    }
 
     Some(NotificationText { title, body })
 }
@@ -0,0 +1,168 @@
+// Copyright 2025. This file is part of TFRAlert.
+
+// TFRAlert is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+// TFRAlert is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License along with TFRAlert. If not, see <https://www.gnu.org/licenses/>.
+
+use anyhow::{Context, Result, bail};
+use log::{info, warn};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+const DATA_DIR: &str = "tfralert";
+
+static DATA_DIR_PATH: OnceLock<PathBuf> = OnceLock::new();
+
+/// `<data dir>/tfralert`, e.g. `~/.local/share/tfralert` on Linux
+fn default_data_dir() -> PathBuf {
+    match dirs::data_dir() {
+        Some(dir) => dir.join(DATA_DIR),
+        None => {
+            warn!("No data directory on this platform, using the working directory");
+            PathBuf::from(".")
+        }
+    }
+}
+
+/// Settle where caches live for the rest of the run: `override_dir` (from
+/// `--data-dir` or `TFRALERT_DATA_DIR`) or the platform default. Creates the
+/// directory, and moves any of `legacy_files` that older versions left in
+/// the working directory. Fails if the run already settled on another one.
+pub fn init_data_dir(override_dir: Option<PathBuf>, legacy_files: &[&str]) -> Result<PathBuf> {
+    let dir = override_dir.unwrap_or_else(default_data_dir);
+    if let Err(dir) = DATA_DIR_PATH.set(dir.clone())
+        && data_dir() != dir
+    {
+        bail!(
+            "Data directory is already {}, can't switch to {}",
+            data_dir().display(),
+            dir.display()
+        );
+    }
+    fs::create_dir_all(&dir)
+        .with_context(|| format!("Failed to create data directory {}", dir.display()))?;
+    migrate_legacy_files(Path::new("."), &dir, legacy_files);
+
+    info!("Data directory {}", dir.display());
+    Ok(dir)
+}
+
+pub fn data_dir() -> &'static Path {
+    DATA_DIR_PATH.get_or_init(default_data_dir)
+}
+
+pub fn data_file(name: &str) -> PathBuf {
+    data_dir().join(name)
+}
+
+/// Move caches from `from` (the working directory), unless the data
+/// directory already has its own (then the old ones are left alone)
+fn migrate_legacy_files(from: &Path, dir: &Path, names: &[&str]) {
+    let same_dir = match (fs::canonicalize(dir), fs::canonicalize(from)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    };
+    if same_dir {
+        return;
+    }
+
+    for name in names {
+        let old = from.join(name);
+        let new = dir.join(name);
+        if !old.is_file() || new.exists() {
+            continue;
+        }
+        // rename fails across filesystems, copy instead
+        let moved = fs::rename(&old, &new).or_else(|_| {
+            fs::copy(&old, &new)?;
+            fs::remove_file(&old)
+        });
+        match moved {
+            Ok(()) => info!("Moved {} to {}", old.display(), new.display()),
+            Err(e) => warn!(
+                "Failed to move {} to {}: {}",
+                old.display(),
+                new.display(),
+                e
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> TempDir {
+            let dir = std::env::temp_dir().join(format!("tfralert-{}-{name}", std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn legacy_files_are_moved() {
+        let temp = TempDir::new("paths-move");
+        let (old, new) = (temp.0.join("old"), temp.0.join("new"));
+        fs::create_dir_all(&old).unwrap();
+        fs::create_dir_all(&new).unwrap();
+        fs::write(old.join("matches.json"), "old matches").unwrap();
+        fs::write(old.join("feed.json"), "old feed").unwrap();
+        fs::write(new.join("feed.json"), "new feed").unwrap();
+
+        migrate_legacy_files(&old, &new, &["matches.json", "feed.json", "missing.json"]);
+        assert!(!old.join("matches.json").exists());
+        assert_eq!(
+            fs::read_to_string(new.join("matches.json")).unwrap(),
+            "old matches"
+        );
+        // the data directory's own copy wins, the old one stays put
+        assert_eq!(
+            fs::read_to_string(new.join("feed.json")).unwrap(),
+            "new feed"
+        );
+        assert_eq!(
+            fs::read_to_string(old.join("feed.json")).unwrap(),
+            "old feed"
+        );
+        assert!(!new.join("missing.json").exists());
+    }
+
+    #[test]
+    fn same_directory_is_left_alone() {
+        let temp = TempDir::new("paths-same");
+        fs::write(temp.0.join("matches.json"), "matches").unwrap();
+        // the same directory, spelled differently
+        let other = temp.0.join(".");
+        migrate_legacy_files(&temp.0, &other, &["matches.json"]);
+        assert_eq!(
+            fs::read_to_string(temp.0.join("matches.json")).unwrap(),
+            "matches"
+        );
+    }
+
+    #[test]
+    fn data_dir_is_settled_once() {
+        let temp = TempDir::new("paths-init");
+        let dir = temp.0.join("data");
+        assert_eq!(init_data_dir(Some(dir.clone()), &[]).unwrap(), dir);
+        assert!(dir.is_dir());
+        assert_eq!(data_dir(), dir);
+        assert!(init_data_dir(Some(dir.clone()), &[]).is_ok());
+        assert!(init_data_dir(Some(temp.0.join("elsewhere")), &[]).is_err());
+        assert_eq!(data_dir(), dir);
+    }
+}
@@ -0,0 +1,284 @@
+// Copyright 2025. This file is part of TFRAlert.
+
+// TFRAlert is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+// TFRAlert is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License along with TFRAlert. If not, see <https://www.gnu.org/licenses/>.
+
+//! Match rules over parsed TFRs. Every rule says why it matched (or didn't),
+//! so the UI and notifications can explain themselves.
+//!
+//! The TFR type filter is not a rule: it applies to the raw feed, before
+//! detail pages are fetched.
+
+use crate::altitude;
+use crate::geo::{self, WatchLocation};
+use crate::keywords::KeywordRule;
+use crate::logic::ParsedTFREvent;
+use chrono::{DateTime, Duration, Utc};
+
+#[derive(Debug, Clone)]
+pub enum MatchRule {
+    /// Every rule must match. Empty matches everything.
+    All(Vec<MatchRule>),
+    /// At least one rule must match. Empty matches nothing.
+    Any(Vec<MatchRule>),
+    Not(Box<MatchRule>),
+    /// One line of keyword rules
+    Keyword(KeywordRule),
+    /// Some area reaches below our operating ceiling
+    Altitude {
+        operating_ceiling_ft: u32,
+        ground_elevation_ft: u32,
+    },
+    /// Some area comes within range of the location
+    Near(WatchLocation),
+    /// In force at some point between now and `hours` from now. `now` is
+    /// the current time unless given, as when replaying a capture.
+    ActiveWithin {
+        hours: u32,
+        now: Option<DateTime<Utc>>,
+    },
+}
+
+/// Whether a rule matched, and why (or why not)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MatchOutcome {
+    pub matched: bool,
+    pub reasons: Vec<String>,
+}
+
+impl MatchOutcome {
+    fn new(matched: bool, reason: String) -> Self {
+        MatchOutcome {
+            matched,
+            reasons: vec![reason],
+        }
+    }
+}
+
+impl MatchRule {
+    pub fn evaluate(&self, event: &ParsedTFREvent) -> MatchOutcome {
+        match self {
+            MatchRule::All(rules) => {
+                let mut reasons = Vec::new();
+                for rule in rules {
+                    let outcome = rule.evaluate(event);
+                    if !outcome.matched {
+                        return outcome;
+                    }
+                    reasons.extend(outcome.reasons);
+                }
+                MatchOutcome {
+                    matched: true,
+                    reasons,
+                }
+            }
+            MatchRule::Any(rules) => {
+                let mut reasons = Vec::new();
+                for rule in rules {
+                    let outcome = rule.evaluate(event);
+                    if outcome.matched {
+                        return outcome;
+                    }
+                    reasons.extend(outcome.reasons);
+                }
+                MatchOutcome {
+                    matched: false,
+                    reasons,
+                }
+            }
+            // only say something when the inner rule tripped
+            MatchRule::Not(rule) => {
+                let inner = rule.evaluate(event);
+                MatchOutcome {
+                    matched: !inner.matched,
+                    reasons: match inner.matched {
+                        true => inner
+                            .reasons
+                            .iter()
+                            .map(|r| format!("excluded by {r}"))
+                            .collect(),
+                        false => Vec::new(),
+                    },
+                }
+            }
+            MatchRule::Keyword(rule) => match rule.matches(event) {
+                true => MatchOutcome::new(true, format!("keyword '{}'", rule.line)),
+                false => MatchOutcome::new(false, format!("no match for keyword '{}'", rule.line)),
+            },
+            MatchRule::Altitude {
+                operating_ceiling_ft,
+                ground_elevation_ft,
+            } => match altitude::overlapping_band(
+                &event.airspace,
+                *operating_ceiling_ft,
+                *ground_elevation_ft,
+            ) {
+                Some(reason) => MatchOutcome::new(true, reason),
+                None => MatchOutcome::new(false, format!("above {operating_ceiling_ft} ft AGL")),
+            },
+            MatchRule::Near(location) => {
+                match geo::near_watch_location(&event.airspace, std::slice::from_ref(location)) {
+                    Some(reason) => MatchOutcome::new(true, reason),
+                    None => MatchOutcome::new(false, format!("not near {}", location.name)),
+                }
+            }
+            MatchRule::ActiveWithin { hours, now } => {
+                if event.active_windows.is_empty() {
+                    return MatchOutcome::new(true, "schedule not parsed, kept".to_string());
+                }
+                let now = now.unwrap_or_else(Utc::now);
+                let until = now + Duration::hours(*hours as i64);
+                match event.active_windows.iter().find(|w| w.overlaps(now, until)) {
+                    Some(w) if w.start <= now => MatchOutcome::new(true, "active now".to_string()),
+                    Some(w) => MatchOutcome::new(
+                        true,
+                        format!("active from {}", w.start.format("%b %d %H:%M UTC")),
+                    ),
+                    None => MatchOutcome::new(false, format!("not active in the next {hours} h")),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keywords::KeywordRules;
+    use crate::logic::{Airspace, AirspaceArea, MatchCriteria};
+
+    fn event(altitude: &str) -> ParsedTFREvent {
+        let mut area = AirspaceArea {
+            altitude: altitude.to_string(),
+            ..Default::default()
+        };
+        area.resolve_altitude();
+        ParsedTFREvent {
+            notam_id: "5/1234".to_string(),
+            description: "Stadium event".to_string(),
+            airspace: Airspace {
+                areas: vec![area],
+                effective: Vec::new(),
+            },
+            ..Default::default()
+        }
+    }
+
+    fn keywords(lines: &[&str]) -> KeywordRules {
+        KeywordRules::parse(&lines.iter().map(|l| l.to_string()).collect::<Vec<_>>()).unwrap()
+    }
+
+    fn criteria(lines: &[&str]) -> MatchCriteria {
+        MatchCriteria {
+            keywords: keywords(lines),
+            operating_ceiling_ft: 1000,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn combinator_reasons() {
+        let rules = keywords(&["stadium", "rodeo"]);
+        let [stadium, rodeo] = [0, 1].map(|i| MatchRule::Keyword(rules.include[i].clone()));
+        let low = event("SFC-3000FT MSL");
+
+        let all = MatchRule::All(vec![stadium.clone(), rodeo.clone()]).evaluate(&low);
+        assert!(!all.matched);
+        assert_eq!(all.reasons, ["no match for keyword 'rodeo'"]);
+
+        let any = MatchRule::Any(vec![rodeo.clone(), stadium.clone()]).evaluate(&low);
+        assert_eq!(
+            any,
+            MatchOutcome::new(true, "keyword 'stadium'".to_string())
+        );
+        let none = MatchRule::Any(vec![rodeo.clone()]).evaluate(&low);
+        assert_eq!(none.reasons, ["no match for keyword 'rodeo'"]);
+
+        let not = MatchRule::Not(Box::new(stadium)).evaluate(&low);
+        assert!(!not.matched);
+        assert_eq!(not.reasons, ["excluded by keyword 'stadium'"]);
+        assert_eq!(
+            MatchRule::Not(Box::new(rodeo)).evaluate(&low),
+            MatchOutcome {
+                matched: true,
+                reasons: Vec::new(),
+            }
+        );
+
+        assert!(MatchRule::All(Vec::new()).evaluate(&low).matched);
+        assert!(!MatchRule::Any(Vec::new()).evaluate(&low).matched);
+    }
+
+    #[test]
+    fn keywords_and_altitude_must_both_match() {
+        let low = event("SFC-3000FT MSL");
+        let high = event("FL180-UNL");
+
+        let outcome = criteria(&["stadium"]).rule().evaluate(&low);
+        assert!(outcome.matched);
+        assert_eq!(
+            outcome.reasons,
+            ["keyword 'stadium'", "altitude SFC–3000 ft MSL"]
+        );
+
+        // a keyword hit doesn't make up for the altitude, or the other way round
+        let outcome = criteria(&["stadium"]).rule().evaluate(&high);
+        assert!(!outcome.matched);
+        assert_eq!(outcome.reasons, ["above 1000 ft AGL"]);
+        assert!(!criteria(&["rodeo"]).rule().evaluate(&low).matched);
+
+        // no keywords: altitude alone decides
+        assert!(criteria(&[]).rule().evaluate(&low).matched);
+        assert!(!criteria(&[]).rule().evaluate(&high).matched);
+
+        // any include line, and no exclude line
+        assert!(
+            criteria(&["rodeo", "stadium"])
+                .rule()
+                .evaluate(&low)
+                .matched
+        );
+        let outcome = criteria(&["stadium", "-event"]).rule().evaluate(&low);
+        assert!(!outcome.matched);
+        assert_eq!(outcome.reasons, ["excluded by keyword '-event'"]);
+    }
+
+    #[test]
+    fn active_within_edges() {
+        use crate::schedule::ActiveWindow;
+
+        let now = "2025-10-17T12:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let rule = MatchRule::ActiveWithin {
+            hours: 72,
+            now: Some(now),
+        };
+        let window = |start: i64, end: Option<i64>| {
+            let mut event = event("SFC-3000FT MSL");
+            event.active_windows = vec![ActiveWindow {
+                start: now + Duration::hours(start),
+                end: end.map(|h| now + Duration::hours(h)),
+            }];
+            rule.evaluate(&event)
+        };
+
+        assert_eq!(window(-1, Some(1)).reasons, ["active now"]);
+        assert_eq!(window(0, Some(1)).reasons, ["active now"]);
+        assert_eq!(window(-1, None).reasons, ["active now"]);
+        assert_eq!(
+            window(72, Some(80)).reasons,
+            ["active from Oct 20 12:00 UTC"]
+        );
+        // just ended, or starting after the window
+        assert_eq!(
+            window(-5, Some(-1)),
+            MatchOutcome::new(false, "not active in the next 72 h".to_string())
+        );
+        assert!(!window(73, None).matched);
+
+        let unscheduled = rule.evaluate(&event("SFC-3000FT MSL"));
+        assert_eq!(unscheduled.reasons, ["schedule not parsed, kept"]);
+    }
+}
@@ -0,0 +1,204 @@
+// Copyright 2025. This file is part of TFRAlert.
+
+// TFRAlert is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+// TFRAlert is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License along with TFRAlert. If not, see <https://www.gnu.org/licenses/>.
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Formats seen on detail pages and in NOTAM text, after "UTC" is stripped
+const DATE_FORMATS: &[&str] = &[
+    "%B %d, %Y at %H%M",
+    "%B %d, %Y %H%M",
+    "%b %d, %Y at %H%M",
+    "%m/%d/%Y %H:%M",
+    "%m/%d/%Y %H%M",
+    "%Y-%m-%d %H:%M",
+    "%Y-%m-%dT%H:%M:%S",
+    "%y%m%d%H%M",
+];
+
+/// When a TFR is in force. No end means permanent / until further notice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ActiveWindow {
+    pub start: DateTime<Utc>,
+    pub end: Option<DateTime<Utc>>,
+}
+
+impl ActiveWindow {
+    /// Is the TFR active at any time between `from` and `to`?
+    pub fn overlaps(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> bool {
+        self.start <= to && self.end.is_none_or(|end| end >= from)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimeError(pub String);
+
+impl std::fmt::Display for TimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unrecognized date/time '{}'", self.0)
+    }
+}
+
+impl std::error::Error for TimeError {}
+
+fn is_open_ended(text: &str) -> bool {
+    let upper = text.trim().to_uppercase();
+    upper.starts_with("PERM") || upper.contains("UFN") || upper.contains("UNTIL FURTHER NOTICE")
+}
+
+/// A single UTC timestamp such as "October 17, 2025 at 1400 UTC" or
+/// "03/16/2025 13:00 UTC". Anything in parentheses (local time) is ignored.
+pub fn parse_timestamp(text: &str) -> Result<DateTime<Utc>, TimeError> {
+    let cleaned = text.split('(').next().unwrap_or("").trim();
+    let cleaned = cleaned
+        .trim_end_matches("UTC")
+        .trim_end_matches('Z')
+        .trim()
+        .trim_end_matches(',')
+        .trim();
+
+    DATE_FORMATS
+        .iter()
+        .find_map(|f| NaiveDateTime::parse_from_str(cleaned, f).ok())
+        .map(|ndt| ndt.and_utc())
+        .ok_or_else(|| TimeError(text.to_string()))
+}
+
+/// End of a window, where "PERMANENT" and "UFN" (or nothing) mean no end
+pub fn parse_end(text: &str) -> Result<Option<DateTime<Utc>>, TimeError> {
+    if text.trim().is_empty() || is_open_ended(text) {
+        return Ok(None);
+    }
+    parse_timestamp(text).map(Some)
+}
+
+/// An "Effective Date(s)" entry: "From October 17, 2025 at 1400 UTC To
+/// October 17, 2025 at 2359 UTC"
+pub fn parse_effective(text: &str) -> Result<ActiveWindow, TimeError> {
+    let err = || TimeError(text.to_string());
+    let lower = text.to_ascii_lowercase();
+
+    let from = lower.find("from").map_or(0, |i| i + "from".len());
+    let to = lower[from..]
+        .find(" to ")
+        .map(|i| from + i)
+        .ok_or_else(err)?;
+
+    Ok(ActiveWindow {
+        start: parse_timestamp(&text[from..to])?,
+        end: parse_end(&text[to + " to ".len()..])?,
+    })
+}
+
+/// Windows from the effective dates if there are any, else the overall
+/// beginning and ending dates
+pub fn active_windows(
+    begin: &str,
+    end: &str,
+    effective: &[String],
+) -> Result<Vec<ActiveWindow>, TimeError> {
+    if !effective.is_empty() {
+        return effective.iter().map(|e| parse_effective(e)).collect();
+    }
+    if begin.is_empty() {
+        return Ok(Vec::new());
+    }
+    Ok(vec![ActiveWindow {
+        start: parse_timestamp(begin)?,
+        end: parse_end(end)?,
+    }])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn utc(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, m, d, h, min, 0).unwrap()
+    }
+
+    #[test]
+    fn timestamps() {
+        let at = utc(2025, 10, 17, 14, 0);
+        for text in [
+            "October 17, 2025 at 1400 UTC",
+            "Oct 17, 2025 at 1400 UTC",
+            "October 17, 2025 1400 UTC (1000 local 10/17/2025)",
+            "10/17/2025 14:00 UTC",
+            "10/17/2025 1400",
+            "2025-10-17 14:00",
+            "2025-10-17T14:00:00Z",
+            "2510171400",
+        ] {
+            assert_eq!(parse_timestamp(text), Ok(at), "{text}");
+        }
+        assert_eq!(
+            parse_timestamp("next Tuesday").unwrap_err().to_string(),
+            "unrecognized date/time 'next Tuesday'"
+        );
+    }
+
+    #[test]
+    fn open_ended() {
+        for text in ["", "PERMANENT", "Permanent", "UFN", "until further notice"] {
+            assert_eq!(parse_end(text), Ok(None), "{text}");
+        }
+        assert_eq!(
+            parse_end("October 17, 2025 at 2359 UTC"),
+            Ok(Some(utc(2025, 10, 17, 23, 59)))
+        );
+        assert!(parse_end("soon").is_err());
+    }
+
+    #[test]
+    fn effective_windows() {
+        let effective = [
+            "From October 17, 2025 at 1400 UTC To October 17, 2025 at 2359 UTC".to_string(),
+            "From October 18, 2025 at 1400 UTC To PERMANENT".to_string(),
+        ];
+        let windows = active_windows("ignored", "ignored", &effective).unwrap();
+        assert_eq!(
+            windows,
+            [
+                ActiveWindow {
+                    start: utc(2025, 10, 17, 14, 0),
+                    end: Some(utc(2025, 10, 17, 23, 59)),
+                },
+                ActiveWindow {
+                    start: utc(2025, 10, 18, 14, 0),
+                    end: None,
+                },
+            ]
+        );
+
+        let overall = active_windows("10/17/2025 14:00", "", &[]).unwrap();
+        assert_eq!(overall[0].end, None);
+        assert!(active_windows("", "", &[]).unwrap().is_empty());
+        assert!(active_windows("", "", &["October 17".to_string()]).is_err());
+    }
+
+    #[test]
+    fn overlap_edges() {
+        let window = ActiveWindow {
+            start: utc(2025, 10, 17, 14, 0),
+            end: Some(utc(2025, 10, 17, 16, 0)),
+        };
+        // touching either end counts
+        assert!(window.overlaps(utc(2025, 10, 17, 16, 0), utc(2025, 10, 18, 0, 0)));
+        assert!(window.overlaps(utc(2025, 10, 17, 0, 0), utc(2025, 10, 17, 14, 0)));
+        assert!(!window.overlaps(utc(2025, 10, 17, 16, 1), utc(2025, 10, 18, 0, 0)));
+        assert!(!window.overlaps(utc(2025, 10, 17, 0, 0), utc(2025, 10, 17, 13, 59)));
+
+        let permanent = ActiveWindow {
+            end: None,
+            ..window
+        };
+        assert!(permanent.overlaps(utc(2030, 1, 1, 0, 0), utc(2030, 1, 2, 0, 0)));
+    }
+}
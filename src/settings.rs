@@ -0,0 +1,251 @@
+// Copyright 2025. This file is part of TFRAlert.
+
+// TFRAlert is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+// TFRAlert is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License along with TFRAlert. If not, see <https://www.gnu.org/licenses/>.
+
+use crate::config::Config;
+use crate::geo::WatchLocation;
+use anyhow::{Context, Result, bail};
+use dioxus::prelude::*;
+
+/// "Field, 38.5, -77.0, 10" per line
+fn format_locations(locations: &[WatchLocation]) -> String {
+    locations
+        .iter()
+        .map(|l| format!("{}, {}, {}, {}", l.name, l.lat, l.lon, l.radius_nm))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn parse_locations(text: &str) -> Result<Vec<WatchLocation>> {
+    text.lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(|line| {
+            // name may itself contain commas, the numbers are always last
+            let mut parts = line.rsplitn(4, ',').map(str::trim);
+            let (Some(radius), Some(lon), Some(lat), Some(name)) =
+                (parts.next(), parts.next(), parts.next(), parts.next())
+            else {
+                bail!("Location '{line}' should look like: name, lat, lon, radius_nm");
+            };
+            let number = |s: &str| {
+                s.parse::<f64>()
+                    .with_context(|| format!("Location '{line}': '{s}' is not a number"))
+            };
+            Ok(WatchLocation {
+                name: name.to_string(),
+                lat: number(lat)?,
+                lon: number(lon)?,
+                radius_nm: number(radius)?,
+            })
+        })
+        .collect()
+}
+
+fn split_list(text: &str, separator: char) -> Vec<String> {
+    text.split(separator)
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// Form for the user-editable parts of the config. `on_save` gets the new,
+/// already persisted config.
+#[component]
+pub fn Settings(config: Config, on_save: EventHandler<Config>, on_cancel: EventHandler) -> Element {
+    let mut keywords = use_signal(|| config.keywords.join("\n"));
+    let mut include_types = use_signal(|| config.types.include.join(", "));
+    let mut exclude_types = use_signal(|| config.types.exclude.join(", "));
+    let mut include_states = use_signal(|| config.states.include.join(", "));
+    let mut exclude_states = use_signal(|| config.states.exclude.join(", "));
+    let mut include_facilities = use_signal(|| config.facilities.include.join(", "));
+    let mut exclude_facilities = use_signal(|| config.facilities.exclude.join(", "));
+    let mut ceiling = use_signal(|| config.operating_ceiling_ft.to_string());
+    let mut lookahead = use_signal(|| config.lookahead_hours.to_string());
+    let mut locations = use_signal(|| format_locations(&config.locations));
+    let mut refresh_minutes = use_signal(|| (config.refresh_seconds / 60).to_string());
+    let mut error = use_signal(|| None::<String>);
+
+    let save = move |_| {
+        let build = || -> Result<Config> {
+            let mut updated = config.clone();
+            updated.keywords = split_list(&keywords(), '\n');
+            updated.types.include = split_list(&include_types(), ',');
+            updated.types.exclude = split_list(&exclude_types(), ',');
+            updated.states.include = split_list(&include_states(), ',');
+            updated.states.exclude = split_list(&exclude_states(), ',');
+            updated.facilities.include = split_list(&include_facilities(), ',');
+            updated.facilities.exclude = split_list(&exclude_facilities(), ',');
+            updated.operating_ceiling_ft = ceiling()
+                .trim()
+                .parse()
+                .context("Altitude ceiling must be a whole number of feet")?;
+            updated.lookahead_hours = lookahead()
+                .trim()
+                .parse()
+                .context("Lookahead must be a whole number of hours")?;
+            updated.locations = parse_locations(&locations())?;
+            let minutes: u64 = refresh_minutes()
+                .trim()
+                .parse()
+                .context("Refresh interval must be a whole number of minutes")?;
+            // leave intervals that aren't whole minutes alone unless edited
+            if minutes != config.refresh_seconds / 60 {
+                updated.refresh_seconds = minutes * 60;
+            }
+            updated.save()?;
+            Ok(updated)
+        };
+
+        match build() {
+            Ok(updated) => {
+                error.set(None);
+                on_save.call(updated);
+            }
+            Err(e) => error.set(Some(format!("{e:#}"))),
+        }
+    };
+
+    rsx! {
+        div { class: "settings",
+            h3 { "Settings" }
+
+            label { "Keyword rules (one per line, empty matches everything)" }
+            textarea {
+                rows: 4,
+                placeholder: "stadium\nreason:vip AND location:\"new york\"\n-exercise",
+                value: "{keywords}",
+                oninput: move |e| keywords.set(e.value()),
+            }
+
+            label { "TFR types to include (comma separated, empty for all)" }
+            input {
+                r#type: "text",
+                placeholder: "SECURITY, VIP, SPACE OPERATIONS, HAZARDS, SPECIAL",
+                value: "{include_types}",
+                oninput: move |e| include_types.set(e.value()),
+            }
+
+            label { "TFR types to exclude (comma separated)" }
+            input {
+                r#type: "text",
+                value: "{exclude_types}",
+                oninput: move |e| exclude_types.set(e.value()),
+            }
+
+            label { "States to include (comma separated, empty for all)" }
+            input {
+                r#type: "text",
+                placeholder: "MD, DC, VA",
+                value: "{include_states}",
+                oninput: move |e| include_states.set(e.value()),
+            }
+
+            label { "States to exclude (comma separated)" }
+            input {
+                r#type: "text",
+                value: "{exclude_states}",
+                oninput: move |e| exclude_states.set(e.value()),
+            }
+
+            label { "Facilities to include (comma separated, empty for all)" }
+            input {
+                r#type: "text",
+                placeholder: "ZDC, ZNY",
+                value: "{include_facilities}",
+                oninput: move |e| include_facilities.set(e.value()),
+            }
+
+            label { "Facilities to exclude (comma separated)" }
+            input {
+                r#type: "text",
+                value: "{exclude_facilities}",
+                oninput: move |e| exclude_facilities.set(e.value()),
+            }
+
+            label { "Operating ceiling (ft AGL)" }
+            input {
+                r#type: "number",
+                min: "0",
+                value: "{ceiling}",
+                oninput: move |e| ceiling.set(e.value()),
+            }
+
+            label { "Only TFRs active within the next (hours, 0 for any time)" }
+            input {
+                r#type: "number",
+                min: "0",
+                value: "{lookahead}",
+                oninput: move |e| lookahead.set(e.value()),
+            }
+
+            label { "Watch locations (name, lat, lon, radius_nm per line)" }
+            textarea {
+                rows: 4,
+                value: "{locations}",
+                oninput: move |e| locations.set(e.value()),
+            }
+
+            label { "Refresh every (minutes)" }
+            input {
+                r#type: "number",
+                min: "1",
+                value: "{refresh_minutes}",
+                oninput: move |e| refresh_minutes.set(e.value()),
+            }
+
+            if let Some(e) = error() {
+                p { class: "settings-error", "{e}" }
+            }
+
+            div { class: "settings-buttons",
+                button { class: "refresh-button", onclick: save, "Save" }
+                button { class: "secondary-button", onclick: move |_| on_cancel.call(()), "Cancel" }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn location_names_may_contain_commas() {
+        let locations =
+            parse_locations("Field, Inc, 38.5, -77.0, 10\n\n  Home,39,-76.5,2.5  ").unwrap();
+        assert_eq!(locations.len(), 2);
+        assert_eq!(locations[0].name, "Field, Inc");
+        assert_eq!(
+            (locations[0].lat, locations[0].lon, locations[0].radius_nm),
+            (38.5, -77.0, 10.0)
+        );
+        assert_eq!(locations[1].name, "Home");
+        assert_eq!(
+            parse_locations(&format_locations(&locations)).unwrap(),
+            locations
+        );
+    }
+
+    #[test]
+    fn bad_location_lines() {
+        let missing = parse_locations("Field, 38.5, -77.0").unwrap_err();
+        assert!(missing.to_string().contains("name, lat, lon, radius_nm"));
+        let not_a_number = parse_locations("Field, 38.5, west, 10").unwrap_err();
+        assert!(not_a_number.to_string().contains("'west' is not a number"));
+    }
+
+    #[test]
+    fn lists_drop_blank_entries() {
+        assert_eq!(split_list(" SECURITY, ,VIP,", ','), ["SECURITY", "VIP"]);
+        assert_eq!(
+            split_list("stadium\n\n  airshow \n", '\n'),
+            ["stadium", "airshow"]
+        );
+        assert!(split_list("  ", ',').is_empty());
+    }
+}
@@ -0,0 +1,272 @@
+// Copyright 2025. This file is part of TFRAlert.
+
+// TFRAlert is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+// TFRAlert is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License along with TFRAlert. If not, see <https://www.gnu.org/licenses/>.
+
+//! Where the feed and detail pages come from: tfr.faa.gov, or a directory
+//! of recorded responses for tests and demos.
+
+use crate::logic::RawTFREvent;
+use anyhow::{Context, Result, bail};
+use flate2::read::GzDecoder;
+use reqwest::Client;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_BASE_URL: &str = "https://tfr.faa.gov";
+const FEED_PATH: &str = "/tfrapi/exportTfrList";
+const DETAIL_PATH: &str = "/tfrapi/getWebText?notamId=";
+
+/// In a replay directory: the feed, and one page per NOTAM under `details`
+pub const FEED_FILE: &str = "exportTfrList.json";
+pub const DETAILS_DIR: &str = "details";
+
+pub trait FeedSource {
+    /// The `exportTfrList` JSON as served
+    async fn feed_text(&self) -> Result<String>;
+    /// The detail page HTML for one NOTAM, e.g. "5/1234"
+    async fn detail_page(&self, notam_id: &str) -> Result<String>;
+
+    /// Every TFR currently listed
+    async fn feed(&self) -> Result<Vec<RawTFREvent>> {
+        serde_json::from_str(&self.feed_text().await?).context("Unexpected feed")
+    }
+}
+
+/// `details/5_1234.html` for "5/1234"
+pub fn detail_file_name(notam_id: &str) -> String {
+    format!("{}.html", notam_id.replace('/', "_"))
+}
+
+/// The FAA's TFR API, or anything serving the same paths
+pub struct HttpSource {
+    client: Client,
+    base_url: String,
+}
+
+impl HttpSource {
+    pub fn new(base_url: &str) -> HttpSource {
+        HttpSource {
+            client: Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+}
+
+impl FeedSource for HttpSource {
+    async fn feed_text(&self) -> Result<String> {
+        let url = format!("{}{}", self.base_url, FEED_PATH);
+        let resp = self.client.get(&url).send().await?.error_for_status()?;
+        Ok(resp.text().await?)
+    }
+
+    async fn detail_page(&self, notam_id: &str) -> Result<String> {
+        let url = format!("{}{}{}", self.base_url, DETAIL_PATH, notam_id);
+        let resp = self.client.get(&url).send().await?.error_for_status()?;
+        Ok(resp.text().await?)
+    }
+}
+
+/// Read a recorded response, compressed (`<path>.gz`) or not
+pub fn read_recorded(path: &Path) -> Result<String> {
+    let mut gz = path.as_os_str().to_os_string();
+    gz.push(".gz");
+    let gz = PathBuf::from(gz);
+    if !gz.exists() {
+        return Ok(fs::read_to_string(path)?);
+    }
+    let mut text = String::new();
+    GzDecoder::new(File::open(&gz)?).read_to_string(&mut text)?;
+    Ok(text)
+}
+
+/// Responses recorded earlier, laid out as `FEED_FILE` and `DETAILS_DIR`.
+/// Detail pages missing from the first directory are looked for in the
+/// others, in order.
+pub struct ReplaySource {
+    dirs: Vec<PathBuf>,
+}
+
+impl ReplaySource {
+    pub fn new(dir: &Path) -> ReplaySource {
+        ReplaySource::layered(vec![dir.to_path_buf()])
+    }
+
+    pub fn layered(dirs: Vec<PathBuf>) -> ReplaySource {
+        ReplaySource { dirs }
+    }
+}
+
+impl FeedSource for ReplaySource {
+    async fn feed_text(&self) -> Result<String> {
+        let path = self
+            .dirs
+            .first()
+            .context("Nothing to replay")?
+            .join(FEED_FILE);
+        read_recorded(&path).with_context(|| format!("No recorded feed at {}", path.display()))
+    }
+
+    async fn detail_page(&self, notam_id: &str) -> Result<String> {
+        let name = detail_file_name(notam_id);
+        for dir in &self.dirs {
+            if let Ok(html) = read_recorded(&dir.join(DETAILS_DIR).join(&name)) {
+                return Ok(html);
+            }
+        }
+        bail!("No recorded detail page for {notam_id}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lifecycle::LifecycleState;
+    use crate::logic::{self, DEFAULT_OPERATING_CEILING_FT, MatchCriteria, TypeFilter};
+    use crate::store::{SqliteStore, TfrStore};
+
+    fn day(name: &str) -> ReplaySource {
+        ReplaySource::new(
+            &Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("tests/fixtures/replay")
+                .join(name),
+        )
+    }
+
+    #[tokio::test]
+    async fn missing_detail_page_is_an_error() {
+        let source = day("day1");
+        assert!(source.detail_page("5/1001").await.is_ok());
+        assert!(source.detail_page("5/9999").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn replayed_days() {
+        let mut store = SqliteStore::in_memory().unwrap();
+        let criteria = MatchCriteria {
+            types: TypeFilter {
+                include: vec!["SECURITY".to_string()],
+                exclude: Vec::new(),
+            },
+            operating_ceiling_ft: DEFAULT_OPERATING_CEILING_FT,
+            ..Default::default()
+        };
+
+        let first = logic::process_feed(&day("day1"), &mut store, &criteria, 0)
+            .await
+            .unwrap();
+        let ids: Vec<_> = first
+            .new_matches
+            .iter()
+            .map(|e| e.notam_id.as_str())
+            .collect();
+        assert_eq!(ids, ["5/1001", "5/1002"]);
+        assert!(first.changes.is_empty());
+
+        let again = logic::process_feed(&day("day1"), &mut store, &criteria, 0)
+            .await
+            .unwrap();
+        assert!(again.new_matches.is_empty());
+        assert!(again.changes.is_empty());
+
+        // 5/1001 is cancelled, 5/1004 replaces 5/1002 with a later end
+        let second = logic::process_feed(&day("day2"), &mut store, &criteria, 0)
+            .await
+            .unwrap();
+        assert_eq!(second.new_matches.len(), 1);
+        let replacement = &second.new_matches[0];
+        assert_eq!(replacement.notam_id, "5/1004");
+        assert_eq!(replacement.supersedes, ["5/1002"]);
+        assert!(replacement.field_changes.iter().any(|c| c.field == "end"));
+
+        assert_eq!(second.changes.len(), 1);
+        assert_eq!(second.changes[0].notam_id, "5/1001");
+        assert_eq!(second.changes[0].state, LifecycleState::Cancelled);
+
+        let matches = store.load_matches().unwrap();
+        let replaced = matches.iter().find(|m| m.notam_id == "5/1002").unwrap();
+        assert_eq!(replaced.superseded_by.as_deref(), Some("5/1004"));
+        let states = store.lifecycle_states().unwrap();
+        assert_eq!(states["5/1002"], LifecycleState::Superseded);
+    }
+
+    #[tokio::test]
+    async fn replaced_tfr_still_listed() {
+        // day 2 with 5/1002 still in the feed next to its replacement
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/replay");
+        let dir = std::env::temp_dir().join(format!("tfralert-{}-overlap", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("details")).unwrap();
+        let feed = |day: &str| -> Vec<serde_json::Value> {
+            let text = fs::read_to_string(fixtures.join(day).join(FEED_FILE)).unwrap();
+            serde_json::from_str(&text).unwrap()
+        };
+        let mut overlap = feed("day1");
+        overlap.retain(|e| e["notam_id"] == "5/1002");
+        overlap.extend(feed("day2"));
+        fs::write(
+            dir.join(FEED_FILE),
+            serde_json::to_string(&overlap).unwrap(),
+        )
+        .unwrap();
+        for (day, page) in [
+            ("day1", "5_1002.html"),
+            ("day2", "5_1003.html"),
+            ("day2", "5_1004.html"),
+        ] {
+            fs::copy(
+                fixtures.join(day).join("details").join(page),
+                dir.join("details").join(page),
+            )
+            .unwrap();
+        }
+
+        let mut store = SqliteStore::in_memory().unwrap();
+        let criteria = MatchCriteria {
+            types: TypeFilter {
+                include: vec!["SECURITY".to_string()],
+                exclude: Vec::new(),
+            },
+            operating_ceiling_ft: DEFAULT_OPERATING_CEILING_FT,
+            ..Default::default()
+        };
+        logic::process_feed(&day("day1"), &mut store, &criteria, 0)
+            .await
+            .unwrap();
+        let replaced = logic::process_feed(&ReplaySource::new(&dir), &mut store, &criteria, 0)
+            .await
+            .unwrap();
+        assert_eq!(replaced.new_matches[0].notam_id, "5/1004");
+        let superseded_by = |store: &SqliteStore| {
+            store
+                .load_matches()
+                .unwrap()
+                .into_iter()
+                .find(|m| m.notam_id == "5/1002")
+                .unwrap()
+                .superseded_by
+        };
+        assert_eq!(superseded_by(&store).as_deref(), Some("5/1004"));
+
+        // one more check with both listed, then 5/1002 leaves the feed
+        let again = logic::process_feed(&ReplaySource::new(&dir), &mut store, &criteria, 0)
+            .await
+            .unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(again.new_matches.is_empty());
+        assert_eq!(superseded_by(&store).as_deref(), Some("5/1004"));
+        let gone = logic::process_feed(&day("day2"), &mut store, &criteria, 0)
+            .await
+            .unwrap();
+        assert!(gone.changes.iter().all(|c| c.notam_id != "5/1002"));
+        assert_eq!(superseded_by(&store).as_deref(), Some("5/1004"));
+        assert_eq!(
+            store.lifecycle_states().unwrap()["5/1002"],
+            LifecycleState::Superseded
+        );
+    }
+}
@@ -0,0 +1,912 @@
+// Copyright 2025. This file is part of TFRAlert.
+
+// TFRAlert is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+// TFRAlert is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License along with TFRAlert. If not, see <https://www.gnu.org/licenses/>.
+
+//! Where downloaded and matched TFRs are kept between runs: an SQLite
+//! database by default, or the original JSON files.
+
+use crate::lifecycle::{LifecycleChange, LifecycleState};
+use crate::logic::{ParsedTFREvent, RawTFREvent};
+use crate::migrate::{self, DATA_VERSION};
+use crate::{cache_file, paths};
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Utc};
+use log::{debug, info};
+use rusqlite::{Connection, OptionalExtension, params};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+const RAW_EVENT_CACHE: &str = "tfr_cache.json";
+const MATCHED_EVENT_CACHE: &str = "tfr_matches.json";
+const NOTIFICATION_HISTORY: &str = "tfr_notifications.json";
+const LIFECYCLE_HISTORY: &str = "tfr_history.json";
+const DATABASE: &str = "tfralert.db";
+/// Feed downloads kept in `feed_snapshots`, a day's worth at the default
+/// refresh interval
+const FEED_SNAPSHOTS_KEPT: i64 = 144;
+/// How long to wait for another process (the GUI or the daemon) to finish
+/// writing before giving up
+const BUSY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// JSON cache files, which older versions kept in the working directory
+pub const JSON_FILES: &[&str] = &[
+    RAW_EVENT_CACHE,
+    MATCHED_EVENT_CACHE,
+    NOTIFICATION_HISTORY,
+    LIFECYCLE_HISTORY,
+];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageKind {
+    #[default]
+    Sqlite,
+    Json,
+}
+
+pub trait TfrStore {
+    /// The last feed download, with details for events we fetched them for
+    fn load_feed(&self) -> Result<Vec<RawTFREvent>>;
+    fn save_feed(&mut self, events: &[RawTFREvent]) -> Result<()>;
+
+    /// Matching events, newest first
+    fn load_matches(&self) -> Result<Vec<ParsedTFREvent>>;
+    /// Replace all matches
+    fn save_matches(&mut self, events: &[ParsedTFREvent]) -> Result<()>;
+
+    fn record_notifications(&mut self, notam_ids: &[String]) -> Result<()>;
+    fn last_notified(&self, notam_id: &str) -> Result<Option<DateTime<Utc>>>;
+
+    fn record_lifecycle(&mut self, changes: &[LifecycleChange]) -> Result<()>;
+    /// Everything that happened to one TFR, oldest first
+    fn lifecycle_history(&self, notam_id: &str) -> Result<Vec<LifecycleChange>>;
+    /// The latest state of every TFR with a history
+    fn lifecycle_states(&self) -> Result<HashMap<String, LifecycleState>>;
+    /// TFRs whose history has gone `Active` at some point
+    fn activated_ids(&self) -> Result<HashSet<String>>;
+
+    /// Problems worth showing the user, such as a cache restored from backup
+    fn take_warnings(&mut self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Put new matches on top
+    fn add_matches(&mut self, events: &[ParsedTFREvent]) -> Result<()> {
+        let mut matches = self.load_matches()?;
+        let new_ids: HashSet<_> = events.iter().map(|e| &e.notam_id).collect();
+        matches.retain(|e| !new_ids.contains(&e.notam_id));
+        matches.splice(0..0, events.iter().cloned());
+        self.save_matches(&matches)
+    }
+
+    fn match_ids(&self) -> Result<HashSet<String>> {
+        Ok(self
+            .load_matches()?
+            .into_iter()
+            .map(|e| e.notam_id)
+            .collect())
+    }
+
+    /// Details for one NOTAM: from the matches, else from the feed
+    fn find_event(&self, notam_id: &str) -> Result<Option<ParsedTFREvent>> {
+        if let Some(event) = self
+            .load_matches()?
+            .into_iter()
+            .find(|e| e.notam_id == notam_id)
+        {
+            return Ok(Some(event));
+        }
+        Ok(self
+            .load_feed()?
+            .into_iter()
+            .filter_map(|e| e.parsed)
+            .find(|e| e.notam_id == notam_id))
+    }
+}
+
+/// Where a store keeps its matches, for showing to the user
+pub fn location(kind: StorageKind) -> PathBuf {
+    match kind {
+        StorageKind::Sqlite => paths::data_file(DATABASE),
+        StorageKind::Json => paths::data_file(MATCHED_EVENT_CACHE),
+    }
+}
+
+/// The store in the data directory. A new database starts with whatever
+/// the JSON files hold.
+pub fn open(kind: StorageKind) -> Result<Box<dyn TfrStore>> {
+    let json = JsonStore::new(paths::data_dir());
+    match kind {
+        StorageKind::Json => Ok(Box::new(json)),
+        StorageKind::Sqlite => {
+            let path = paths::data_file(DATABASE);
+            let fresh = !path.exists();
+            let mut store = SqliteStore::open(&path)?;
+            if fresh && json.has_data() {
+                import_json(&mut store, &json)?;
+            }
+            Ok(Box::new(store))
+        }
+    }
+}
+
+/// Everything the JSON caches hold, into a new database
+fn import_json(store: &mut SqliteStore, json: &JsonStore) -> Result<()> {
+    let matches = json.load_matches()?;
+    store.save_feed(&json.load_feed()?)?;
+    store.save_matches(&matches)?;
+    store.import_notifications(&json.read(NOTIFICATION_HISTORY)?)?;
+    store.record_lifecycle(&json.read::<LifecycleChange>(LIFECYCLE_HISTORY)?)?;
+    info!("Imported {} matches from JSON caches", matches.len());
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct NotificationRecord {
+    notam_id: String,
+    sent_at: DateTime<Utc>,
+}
+
+/// Pretty-printed JSON, one file each, rewritten on every save
+pub struct JsonStore {
+    dir: PathBuf,
+    /// Caches that had to be recovered since the last `take_warnings`
+    warnings: RefCell<Vec<String>>,
+}
+
+impl JsonStore {
+    pub fn new(dir: &Path) -> JsonStore {
+        JsonStore {
+            dir: dir.to_path_buf(),
+            warnings: RefCell::new(Vec::new()),
+        }
+    }
+
+    fn has_data(&self) -> bool {
+        [RAW_EVENT_CACHE, MATCHED_EVENT_CACHE]
+            .iter()
+            .any(|name| self.dir.join(name).exists())
+    }
+
+    fn read<T: DeserializeOwned>(&self, name: &str) -> Result<Vec<T>> {
+        let loaded = cache_file::load(&self.dir.join(name))?;
+        if let Some(warning) = loaded.warning {
+            self.warnings.borrow_mut().push(warning);
+        }
+        Ok(loaded.data)
+    }
+
+    fn write<T: Serialize>(&self, name: &str, data: &[T]) -> Result<()> {
+        cache_file::save(&self.dir.join(name), data)
+    }
+}
+
+impl TfrStore for JsonStore {
+    fn load_feed(&self) -> Result<Vec<RawTFREvent>> {
+        self.read(RAW_EVENT_CACHE)
+    }
+
+    fn save_feed(&mut self, events: &[RawTFREvent]) -> Result<()> {
+        self.write(RAW_EVENT_CACHE, events)
+    }
+
+    fn load_matches(&self) -> Result<Vec<ParsedTFREvent>> {
+        self.read(MATCHED_EVENT_CACHE)
+    }
+
+    fn save_matches(&mut self, events: &[ParsedTFREvent]) -> Result<()> {
+        self.write(MATCHED_EVENT_CACHE, events)
+    }
+
+    fn record_notifications(&mut self, notam_ids: &[String]) -> Result<()> {
+        let mut history: Vec<NotificationRecord> = self.read(NOTIFICATION_HISTORY)?;
+        let sent_at = Utc::now();
+        history.extend(notam_ids.iter().map(|id| NotificationRecord {
+            notam_id: id.clone(),
+            sent_at,
+        }));
+        self.write(NOTIFICATION_HISTORY, &history)
+    }
+
+    fn last_notified(&self, notam_id: &str) -> Result<Option<DateTime<Utc>>> {
+        let history: Vec<NotificationRecord> = self.read(NOTIFICATION_HISTORY)?;
+        Ok(history
+            .into_iter()
+            .filter(|r| r.notam_id == notam_id)
+            .map(|r| r.sent_at)
+            .max())
+    }
+
+    fn record_lifecycle(&mut self, changes: &[LifecycleChange]) -> Result<()> {
+        if changes.is_empty() {
+            return Ok(());
+        }
+        let mut history: Vec<LifecycleChange> = self.read(LIFECYCLE_HISTORY)?;
+        history.extend_from_slice(changes);
+        self.write(LIFECYCLE_HISTORY, &history)
+    }
+
+    fn lifecycle_history(&self, notam_id: &str) -> Result<Vec<LifecycleChange>> {
+        let history: Vec<LifecycleChange> = self.read(LIFECYCLE_HISTORY)?;
+        Ok(history
+            .into_iter()
+            .filter(|c| c.notam_id == notam_id)
+            .collect())
+    }
+
+    fn lifecycle_states(&self) -> Result<HashMap<String, LifecycleState>> {
+        let history: Vec<LifecycleChange> = self.read(LIFECYCLE_HISTORY)?;
+        Ok(history.into_iter().map(|c| (c.notam_id, c.state)).collect())
+    }
+
+    fn activated_ids(&self) -> Result<HashSet<String>> {
+        let history: Vec<LifecycleChange> = self.read(LIFECYCLE_HISTORY)?;
+        Ok(history
+            .into_iter()
+            .filter(|c| c.state == LifecycleState::Active)
+            .map(|c| c.notam_id)
+            .collect())
+    }
+
+    fn take_warnings(&mut self) -> Vec<String> {
+        self.warnings.take()
+    }
+}
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS feed (
+    notam_id TEXT PRIMARY KEY,
+    position INTEGER NOT NULL,
+    data TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS feed_snapshots (
+    fetched_at TEXT PRIMARY KEY,
+    data TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS events (
+    notam_id TEXT PRIMARY KEY,
+    data TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS matches (
+    notam_id TEXT PRIMARY KEY,
+    position INTEGER NOT NULL,
+    reasons TEXT NOT NULL,
+    matched_at TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS matches_position ON matches (position);
+CREATE TABLE IF NOT EXISTS notifications (
+    id INTEGER PRIMARY KEY,
+    notam_id TEXT NOT NULL,
+    sent_at TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS notifications_notam_id ON notifications (notam_id);
+CREATE TABLE IF NOT EXISTS history (
+    id INTEGER PRIMARY KEY,
+    notam_id TEXT NOT NULL,
+    state TEXT NOT NULL,
+    at TEXT NOT NULL,
+    location TEXT NOT NULL,
+    detail TEXT NOT NULL,
+    changes TEXT NOT NULL DEFAULT '[]'
+);
+CREATE INDEX IF NOT EXISTS history_notam_id ON history (notam_id);
+";
+
+/// Feed downloads, parsed details, match results, notification and
+/// lifecycle history in one database, keyed by `notam_id`. `feed` is the
+/// latest download and `feed_snapshots` the last `FEED_SNAPSHOTS_KEPT` as
+/// received, by fetch time. Details live in `events` only, so the feed and
+/// matches share them, until neither they nor a snapshot list the TFR.
+pub struct SqliteStore {
+    conn: Connection,
+}
+
+impl SqliteStore {
+    pub fn open(path: &Path) -> Result<SqliteStore> {
+        let conn = Connection::open(path)
+            .with_context(|| format!("Failed to open database {}", path.display()))?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        SqliteStore::with_connection(conn)
+    }
+
+    /// Nothing on disk, for tests and replays
+    pub fn in_memory() -> Result<SqliteStore> {
+        SqliteStore::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(mut conn: Connection) -> Result<SqliteStore> {
+        conn.execute_batch(SCHEMA)
+            .context("Failed to create database tables")?;
+        SqliteStore::add_column(&conn, "history", "changes", "TEXT NOT NULL DEFAULT '[]'")?;
+        SqliteStore::upgrade(&mut conn)?;
+        Ok(SqliteStore { conn })
+    }
+
+    /// For tables created before `column` was added to `SCHEMA`
+    fn add_column(conn: &Connection, table: &str, column: &str, definition: &str) -> Result<()> {
+        let exists = conn
+            .prepare(&format!(
+                "SELECT 1 FROM pragma_table_info('{table}') WHERE name = ?1"
+            ))?
+            .exists(params![column])?;
+        if !exists {
+            conn.execute_batch(&format!(
+                "ALTER TABLE {table} ADD COLUMN {column} {definition}"
+            ))?;
+        }
+        Ok(())
+    }
+
+    /// Bring the JSON in `feed` and `events` up to `DATA_VERSION`, which is
+    /// kept in `user_version`. Databases from before it was set hold
+    /// version 1.
+    fn upgrade(conn: &mut Connection) -> Result<()> {
+        let version: u64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        let version = version.max(1);
+        if version > DATA_VERSION {
+            bail!("Database was written by a newer TFRAlert (version {version})");
+        }
+        if version == DATA_VERSION {
+            conn.pragma_update(None, "user_version", DATA_VERSION)?;
+            return Ok(());
+        }
+
+        let tx = conn.transaction()?;
+        let snapshots = tx
+            .prepare("SELECT fetched_at, data FROM feed_snapshots")?
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        for (fetched_at, data) in snapshots {
+            let mut items: Vec<serde_json::Value> = serde_json::from_str(&data)?;
+            for item in &mut items {
+                migrate::upgrade(item, version)
+                    .with_context(|| format!("Failed to upgrade feed snapshot {fetched_at}"))?;
+            }
+            tx.execute(
+                "UPDATE feed_snapshots SET data = ?1 WHERE fetched_at = ?2",
+                params![serde_json::to_string(&items)?, fetched_at],
+            )?;
+        }
+        for table in ["feed", "events"] {
+            let rows = tx
+                .prepare(&format!("SELECT notam_id, data FROM {table}"))?
+                .query_map([], |row| {
+                    Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            for (notam_id, data) in rows {
+                let mut item = serde_json::from_str(&data)?;
+                migrate::upgrade(&mut item, version)
+                    .with_context(|| format!("Failed to upgrade {table} row {notam_id}"))?;
+                tx.execute(
+                    &format!("UPDATE {table} SET data = ?1 WHERE notam_id = ?2"),
+                    params![serde_json::to_string(&item)?, notam_id],
+                )?;
+            }
+        }
+        tx.pragma_update(None, "user_version", DATA_VERSION)?;
+        tx.commit()?;
+        info!("Upgraded database from version {version} to {DATA_VERSION}");
+        Ok(())
+    }
+
+    /// Notifications sent before, keeping when they were sent
+    fn import_notifications(&mut self, records: &[NotificationRecord]) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        for r in records {
+            tx.execute(
+                "INSERT INTO notifications (notam_id, sent_at) VALUES (?1, ?2)",
+                params![r.notam_id, r.sent_at.to_rfc3339()],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Drop details of TFRs that are no longer in the feed, a kept feed
+    /// snapshot, or the matches
+    fn prune_events(conn: &Connection) -> Result<()> {
+        let pruned = conn.execute(
+            "DELETE FROM events WHERE notam_id NOT IN (SELECT notam_id FROM feed)
+             AND notam_id NOT IN (SELECT notam_id FROM matches)
+             AND notam_id NOT IN (
+                 SELECT json_extract(item.value, '$.notam_id')
+                 FROM feed_snapshots, json_each(feed_snapshots.data) AS item
+                 WHERE json_extract(item.value, '$.notam_id') IS NOT NULL
+             )",
+            [],
+        )?;
+        if pruned > 0 {
+            debug!("Pruned details of {pruned} TFRs");
+        }
+        Ok(())
+    }
+
+    fn save_event(conn: &Connection, event: &ParsedTFREvent) -> Result<()> {
+        conn.execute(
+            "INSERT INTO events (notam_id, data, updated_at) VALUES (?1, ?2, ?3)
+             ON CONFLICT (notam_id) DO UPDATE SET data = excluded.data, updated_at = excluded.updated_at",
+            params![
+                event.notam_id,
+                serde_json::to_string(event)?,
+                Utc::now().to_rfc3339()
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn save_match(conn: &Connection, event: &ParsedTFREvent, position: i64) -> Result<()> {
+        SqliteStore::save_event(conn, event)?;
+        conn.execute(
+            "INSERT INTO matches (notam_id, position, reasons, matched_at) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT (notam_id) DO UPDATE SET position = excluded.position, reasons = excluded.reasons",
+            params![
+                event.notam_id,
+                position,
+                serde_json::to_string(&event.match_reasons)?,
+                Utc::now().to_rfc3339()
+            ],
+        )?;
+        Ok(())
+    }
+}
+
+/// An event from `events`, with its reasons from `matches` if it matched
+fn event_from_row(data: &str, reasons: Option<&str>) -> Result<ParsedTFREvent> {
+    let mut event: ParsedTFREvent = serde_json::from_str(data)?;
+    if let Some(reasons) = reasons {
+        event.match_reasons = serde_json::from_str(reasons)?;
+    }
+    Ok(event)
+}
+
+impl TfrStore for SqliteStore {
+    fn load_feed(&self) -> Result<Vec<RawTFREvent>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT f.data, e.data FROM feed f LEFT JOIN events e ON e.notam_id = f.notam_id
+             ORDER BY f.position",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?))
+        })?;
+        let mut events = Vec::new();
+        for row in rows {
+            let (raw, details) = row?;
+            let mut event: RawTFREvent = serde_json::from_str(&raw)?;
+            event.parsed = details.map(|d| event_from_row(&d, None)).transpose()?;
+            events.push(event);
+        }
+        Ok(events)
+    }
+
+    fn save_feed(&mut self, events: &[RawTFREvent]) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        tx.execute("DELETE FROM feed", [])?;
+        let mut snapshot = Vec::with_capacity(events.len());
+        for (position, event) in events.iter().enumerate() {
+            if let Some(parsed) = &event.parsed {
+                SqliteStore::save_event(&tx, parsed)?;
+            }
+            let raw = RawTFREvent {
+                parsed: None,
+                ..event.clone()
+            };
+            tx.execute(
+                "INSERT INTO feed (notam_id, position, data) VALUES (?1, ?2, ?3)",
+                params![
+                    event.notam_id,
+                    position as i64,
+                    serde_json::to_string(&raw)?
+                ],
+            )?;
+            snapshot.push(raw);
+        }
+        tx.execute(
+            "INSERT OR REPLACE INTO feed_snapshots (fetched_at, data) VALUES (?1, ?2)",
+            params![Utc::now().to_rfc3339(), serde_json::to_string(&snapshot)?],
+        )?;
+        tx.execute(
+            "DELETE FROM feed_snapshots WHERE fetched_at NOT IN
+             (SELECT fetched_at FROM feed_snapshots ORDER BY fetched_at DESC LIMIT ?1)",
+            params![FEED_SNAPSHOTS_KEPT],
+        )?;
+        SqliteStore::prune_events(&tx)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn load_matches(&self) -> Result<Vec<ParsedTFREvent>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT e.data, m.reasons FROM matches m JOIN events e ON e.notam_id = m.notam_id
+             ORDER BY m.position",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
+        let mut events = Vec::new();
+        for row in rows {
+            let (data, reasons) = row?;
+            events.push(event_from_row(&data, Some(&reasons))?);
+        }
+        Ok(events)
+    }
+
+    fn save_matches(&mut self, events: &[ParsedTFREvent]) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        // upsert rather than clear, so `matched_at` survives a re-filter
+        let keep: HashSet<_> = events.iter().map(|e| e.notam_id.as_str()).collect();
+        let existing: Vec<String> = tx
+            .prepare("SELECT notam_id FROM matches")?
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<_, _>>()?;
+        for id in existing.iter().filter(|id| !keep.contains(id.as_str())) {
+            tx.execute("DELETE FROM matches WHERE notam_id = ?1", params![id])?;
+        }
+        for (position, event) in events.iter().enumerate() {
+            SqliteStore::save_match(&tx, event, position as i64)?;
+        }
+        SqliteStore::prune_events(&tx)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn add_matches(&mut self, events: &[ParsedTFREvent]) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        let top: i64 = tx.query_row(
+            "SELECT COALESCE(MIN(position), 0) FROM matches",
+            [],
+            |row| row.get(0),
+        )?;
+        let first = top - events.len() as i64;
+        for (i, event) in events.iter().enumerate() {
+            SqliteStore::save_match(&tx, event, first + i as i64)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn match_ids(&self) -> Result<HashSet<String>> {
+        let mut stmt = self.conn.prepare("SELECT notam_id FROM matches")?;
+        let ids = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<_, _>>()?;
+        Ok(ids)
+    }
+
+    fn find_event(&self, notam_id: &str) -> Result<Option<ParsedTFREvent>> {
+        let row = self
+            .conn
+            .query_row(
+                "SELECT e.data, m.reasons FROM events e LEFT JOIN matches m ON m.notam_id = e.notam_id
+                 WHERE e.notam_id = ?1",
+                params![notam_id],
+                |row| Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?)),
+            )
+            .optional()?;
+        row.map(|(data, reasons)| event_from_row(&data, reasons.as_deref()))
+            .transpose()
+    }
+
+    fn record_notifications(&mut self, notam_ids: &[String]) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        let sent_at = Utc::now().to_rfc3339();
+        for id in notam_ids {
+            tx.execute(
+                "INSERT INTO notifications (notam_id, sent_at) VALUES (?1, ?2)",
+                params![id, sent_at],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn last_notified(&self, notam_id: &str) -> Result<Option<DateTime<Utc>>> {
+        let sent_at: Option<String> = self.conn.query_row(
+            "SELECT MAX(sent_at) FROM notifications WHERE notam_id = ?1",
+            params![notam_id],
+            |row| row.get(0),
+        )?;
+        sent_at.map(|s| parse_time(&s)).transpose()
+    }
+
+    fn record_lifecycle(&mut self, changes: &[LifecycleChange]) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        for c in changes {
+            tx.execute(
+                "INSERT INTO history (notam_id, state, at, location, detail, changes)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    c.notam_id,
+                    c.state.as_str(),
+                    c.at.to_rfc3339(),
+                    c.location,
+                    c.detail,
+                    serde_json::to_string(&c.changes)?
+                ],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn lifecycle_history(&self, notam_id: &str) -> Result<Vec<LifecycleChange>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT state, at, location, detail, changes FROM history WHERE notam_id = ?1
+             ORDER BY id",
+        )?;
+        let rows = stmt.query_map(params![notam_id], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, String>(4)?,
+            ))
+        })?;
+        let mut history = Vec::new();
+        for row in rows {
+            let (state, at, location, detail, changes) = row?;
+            history.push(LifecycleChange {
+                notam_id: notam_id.to_string(),
+                state: parse_state(&state)?,
+                at: parse_time(&at)?,
+                location,
+                detail,
+                changes: serde_json::from_str(&changes)?,
+            });
+        }
+        Ok(history)
+    }
+
+    fn lifecycle_states(&self) -> Result<HashMap<String, LifecycleState>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT notam_id, state FROM history h
+             WHERE id = (SELECT MAX(id) FROM history WHERE notam_id = h.notam_id)",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
+        let mut states = HashMap::new();
+        for row in rows {
+            let (notam_id, state) = row?;
+            states.insert(notam_id, parse_state(&state)?);
+        }
+        Ok(states)
+    }
+
+    fn activated_ids(&self) -> Result<HashSet<String>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT DISTINCT notam_id FROM history WHERE state = ?1")?;
+        let ids = stmt
+            .query_map(params![LifecycleState::Active.as_str()], |row| row.get(0))?
+            .collect::<Result<_, _>>()?;
+        Ok(ids)
+    }
+}
+
+fn parse_time(text: &str) -> Result<DateTime<Utc>> {
+    Ok(DateTime::parse_from_rfc3339(text)?.with_timezone(&Utc))
+}
+
+fn parse_state(text: &str) -> Result<LifecycleState> {
+    LifecycleState::parse(text).with_context(|| format!("Unknown lifecycle state '{text}'"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(id: &str) -> ParsedTFREvent {
+        ParsedTFREvent {
+            notam_id: id.to_string(),
+            location: "ANNAPOLIS, MD".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn raw(id: &str, parsed: Option<ParsedTFREvent>) -> RawTFREvent {
+        RawTFREvent {
+            notam_id: id.to_string(),
+            r#type: Some("SECURITY".to_string()),
+            parsed,
+            ..Default::default()
+        }
+    }
+
+    fn ids(events: &[ParsedTFREvent]) -> Vec<&str> {
+        events.iter().map(|e| e.notam_id.as_str()).collect()
+    }
+
+    #[test]
+    fn feed_keeps_order_and_details() {
+        let mut store = SqliteStore::in_memory().unwrap();
+        store
+            .save_feed(&[raw("5/2", Some(event("5/2"))), raw("5/1", None)])
+            .unwrap();
+
+        let feed = store.load_feed().unwrap();
+        assert_eq!(feed.len(), 2);
+        assert_eq!(feed[0].notam_id, "5/2");
+        assert_eq!(feed[0].parsed.as_ref().unwrap().location, "ANNAPOLIS, MD");
+        assert!(feed[1].parsed.is_none());
+        assert!(store.find_event("5/2").unwrap().is_some());
+        assert!(store.find_event("5/9").unwrap().is_none());
+    }
+
+    #[test]
+    fn feed_snapshots_are_kept_up_to_the_limit() {
+        let mut store = SqliteStore::in_memory().unwrap();
+        let count = |store: &SqliteStore| -> i64 {
+            store
+                .conn
+                .query_row("SELECT COUNT(*) FROM feed_snapshots", [], |row| row.get(0))
+                .unwrap()
+        };
+        store.save_feed(&[raw("5/1", Some(event("5/1")))]).unwrap();
+        store.save_feed(&[raw("5/2", None)]).unwrap();
+        assert_eq!(count(&store), 2);
+        // the latest is the current feed, the first is still on record
+        assert_eq!(store.load_feed().unwrap()[0].notam_id, "5/2");
+        let first: String = store
+            .conn
+            .query_row(
+                "SELECT data FROM feed_snapshots ORDER BY fetched_at LIMIT 1",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        let first: Vec<RawTFREvent> = serde_json::from_str(&first).unwrap();
+        assert_eq!(first[0].notam_id, "5/1");
+        assert!(first[0].parsed.is_none());
+
+        for _ in 0..FEED_SNAPSHOTS_KEPT {
+            store.save_feed(&[raw("5/3", None)]).unwrap();
+        }
+        assert_eq!(count(&store), FEED_SNAPSHOTS_KEPT);
+    }
+
+    #[test]
+    fn feed_keeps_fields_we_dont_model() {
+        let entry = r#"{"notam_id": "5/1", "type": "SECURITY", "state": "MD",
+            "facility": "ZDC", "gid": 1234567, "mod_abs_time": "202510011432",
+            "center_coords": "385837N0762910W"}"#;
+        let event: RawTFREvent = serde_json::from_str(entry).unwrap();
+        assert_eq!(event.gid.as_deref(), Some("1234567"));
+        assert_eq!(event.state.as_deref(), Some("MD"));
+
+        let mut store = SqliteStore::in_memory().unwrap();
+        store.save_feed(&[event]).unwrap();
+        let feed = store.load_feed().unwrap();
+        assert_eq!(feed[0].facility.as_deref(), Some("ZDC"));
+        assert_eq!(feed[0].extra["center_coords"], "385837N0762910W");
+    }
+
+    #[test]
+    fn new_matches_go_on_top() {
+        let mut store = SqliteStore::in_memory().unwrap();
+        store.save_matches(&[event("5/1")]).unwrap();
+        let mut newer = event("5/2");
+        newer.match_reasons = vec!["keyword 'stadium'".to_string()];
+        store.add_matches(&[newer, event("5/3")]).unwrap();
+
+        let matches = store.load_matches().unwrap();
+        assert_eq!(ids(&matches), ["5/2", "5/3", "5/1"]);
+        assert_eq!(matches[0].match_reasons, ["keyword 'stadium'"]);
+        assert!(store.match_ids().unwrap().contains("5/3"));
+
+        store.save_matches(&[event("5/1")]).unwrap();
+        assert_eq!(ids(&store.load_matches().unwrap()), ["5/1"]);
+    }
+
+    #[test]
+    fn unreferenced_details_are_pruned() {
+        let mut store = SqliteStore::in_memory().unwrap();
+        let details = |store: &SqliteStore| -> i64 {
+            store
+                .conn
+                .query_row("SELECT COUNT(*) FROM events", [], |row| row.get(0))
+                .unwrap()
+        };
+        store
+            .save_feed(&[
+                raw("5/1", Some(event("5/1"))),
+                raw("5/2", Some(event("5/2"))),
+            ])
+            .unwrap();
+        store.save_matches(&[event("5/2")]).unwrap();
+        store.save_feed(&[raw("5/3", None)]).unwrap();
+        // 5/1 is still in a kept snapshot
+        assert_eq!(details(&store), 2);
+
+        for _ in 0..FEED_SNAPSHOTS_KEPT {
+            store.save_feed(&[raw("5/3", None)]).unwrap();
+        }
+        assert_eq!(details(&store), 1);
+        assert!(store.find_event("5/1").unwrap().is_none());
+        assert_eq!(ids(&store.load_matches().unwrap()), ["5/2"]);
+
+        store.save_matches(&[]).unwrap();
+        assert_eq!(details(&store), 0);
+    }
+
+    #[test]
+    fn json_caches_are_imported() {
+        let dir = std::env::temp_dir().join(format!("tfralert-{}-import", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut json = JsonStore::new(&dir);
+        json.save_feed(&[raw("5/1", Some(event("5/1")))]).unwrap();
+        json.save_matches(&[event("5/1")]).unwrap();
+        json.record_notifications(&["5/1".to_string()]).unwrap();
+        let sent_at = json.last_notified("5/1").unwrap();
+
+        let mut store = SqliteStore::in_memory().unwrap();
+        import_json(&mut store, &json).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(ids(&store.load_matches().unwrap()), ["5/1"]);
+        assert_eq!(store.load_feed().unwrap().len(), 1);
+        assert!(sent_at.is_some());
+        assert_eq!(store.last_notified("5/1").unwrap(), sent_at);
+    }
+
+    #[test]
+    fn notification_history() {
+        let mut store = SqliteStore::in_memory().unwrap();
+        assert!(store.last_notified("5/1").unwrap().is_none());
+        store.record_notifications(&["5/1".to_string()]).unwrap();
+        assert!(store.last_notified("5/1").unwrap().is_some());
+        assert!(store.last_notified("5/2").unwrap().is_none());
+    }
+
+    #[test]
+    fn lifecycle_history() {
+        let mut store = SqliteStore::in_memory().unwrap();
+        let change = |id: &str, state| LifecycleChange {
+            notam_id: id.to_string(),
+            state,
+            at: Utc::now(),
+            location: "ANNAPOLIS, MD".to_string(),
+            detail: String::new(),
+            changes: Vec::new(),
+        };
+        store
+            .record_lifecycle(&[
+                change("5/1", LifecycleState::New),
+                change("5/2", LifecycleState::New),
+            ])
+            .unwrap();
+        store
+            .record_lifecycle(&[change("5/1", LifecycleState::Cancelled)])
+            .unwrap();
+
+        let history = store.lifecycle_history("5/1").unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[1].state, LifecycleState::Cancelled);
+        assert_eq!(history[1].location, "ANNAPOLIS, MD");
+
+        let states = store.lifecycle_states().unwrap();
+        assert_eq!(states["5/1"], LifecycleState::Cancelled);
+        assert_eq!(states["5/2"], LifecycleState::New);
+
+        store
+            .record_lifecycle(&[
+                change("5/2", LifecycleState::Active),
+                change("5/2", LifecycleState::Modified),
+            ])
+            .unwrap();
+        assert_eq!(
+            store.activated_ids().unwrap(),
+            HashSet::from(["5/2".to_string()])
+        );
+    }
+}